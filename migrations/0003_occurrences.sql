@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS occurrences (
+    id              BIGSERIAL PRIMARY KEY NOT NULL,
+    task_id         BIGINT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    user_id         TEXT NOT NULL,
+    -- when the occurrence was scheduled for, not when it was sent
+    due_at          TIMESTAMPTZ NOT NULL,
+    -- one of: pending, done, missed
+    status          TEXT NOT NULL DEFAULT 'pending',
+    resolved_at     TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS occurrences_task_id ON occurrences (task_id, due_at);
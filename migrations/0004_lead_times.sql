@@ -0,0 +1,2 @@
+-- how long before each occurrence a heads-up reminder is sent, in seconds
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS lead_secs INT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,9 @@
+-- number of checklist items in the task info when the occurrence fired
+ALTER TABLE occurrences ADD COLUMN IF NOT EXISTS checklist_len INT NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS checklist_progress (
+    occurrence_id   BIGINT NOT NULL REFERENCES occurrences(id) ON DELETE CASCADE,
+    -- 0-indexed position of the item in the checklist
+    item            INT NOT NULL,
+    PRIMARY KEY (occurrence_id, item)
+);
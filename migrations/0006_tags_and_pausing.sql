@@ -0,0 +1,3 @@
+-- lowercase, without the leading #
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS paused BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+-- one of: silent, ping, nag
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS priority TEXT NOT NULL DEFAULT 'nag';
@@ -0,0 +1,11 @@
+-- one of: once, recurring, deadline
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS kind TEXT;
+UPDATE tasks SET kind = CASE WHEN on_date IS NOT NULL THEN 'once' ELSE 'recurring' END
+WHERE kind IS NULL;
+ALTER TABLE tasks ALTER COLUMN kind SET NOT NULL;
+
+-- deadline tasks remind this long before the deadline, in seconds
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS offset_secs INT[] NOT NULL DEFAULT '{}';
+
+-- copied onto new deadline tasks. defaults to 7d, 1d and 3h
+ALTER TABLE settings ADD COLUMN IF NOT EXISTS deadline_offset_secs INT[] NOT NULL DEFAULT '{604800,86400,10800}';
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS templates (
+    user_id         TEXT NOT NULL,
+    name            TEXT NOT NULL,
+    title           TEXT NOT NULL,
+    info            TEXT NOT NULL,
+    -- the last line of a task, see time_parse.rs
+    time_line       TEXT NOT NULL,
+    PRIMARY KEY (user_id, name)
+);
@@ -0,0 +1,12 @@
+-- recent task changes per user, so they can be undone
+CREATE TABLE IF NOT EXISTS action_log (
+    id              BIGSERIAL PRIMARY KEY NOT NULL,
+    user_id         TEXT NOT NULL,
+    -- create, delete, edit, pause or resume
+    action          TEXT NOT NULL,
+    -- the task rows before the action, or after it for creates
+    tasks           JSONB NOT NULL,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS action_log_user_id ON action_log (user_id, id);
@@ -0,0 +1,6 @@
+-- finished tasks are archived instead of deleted, so their history stays around
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+-- completed, cancelled or expired
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS archive_reason TEXT;
+
+CREATE INDEX IF NOT EXISTS tasks_archived ON tasks (user_id, archived_at);
@@ -0,0 +1,12 @@
+-- files sent along with a task, stored here since discord attachment links expire
+CREATE TABLE IF NOT EXISTS attachments (
+    id              BIGSERIAL PRIMARY KEY NOT NULL,
+    task_id         BIGINT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    filename        TEXT NOT NULL,
+    content_type    TEXT,
+    data            BYTEA NOT NULL,
+    -- set when an edit swapped the file out, it's kept until the edit can't be undone anymore
+    replaced_at     TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS attachments_task_id ON attachments (task_id);
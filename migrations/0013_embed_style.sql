@@ -0,0 +1,7 @@
+-- how the reminder embed looks, parsed from `key: value` lines in the info
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS colour INT;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS url TEXT;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS thumbnail TEXT;
+-- embed fields, names[i] goes with values[i]
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS field_names TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS field_values TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,7 @@
+-- chained tasks run `delay_secs` after an occurrence of `after_task` is done
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS after_task BIGINT REFERENCES tasks(id) ON DELETE CASCADE;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS delay_secs INT;
+-- set when the task it's chained to gets done, NULL while waiting
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS fire_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS tasks_after_task ON tasks (after_task);
@@ -0,0 +1,5 @@
+-- how the spammer nags until a reminder is acknowledged
+ALTER TABLE settings ADD COLUMN IF NOT EXISTS nag_interval_ms INT NOT NULL DEFAULT 1500;
+ALTER TABLE settings ADD COLUMN IF NOT EXISTS nag_text TEXT NOT NULL DEFAULT 'hey buddy';
+-- delete each ping right after sending it
+ALTER TABLE settings ADD COLUMN IF NOT EXISTS ghost_ping BOOLEAN NOT NULL DEFAULT true;
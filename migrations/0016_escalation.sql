@@ -0,0 +1,4 @@
+-- nags start at this interval and speed up to nag_interval_ms
+ALTER TABLE settings ADD COLUMN IF NOT EXISTS nag_start_interval_ms INT NOT NULL DEFAULT 60000;
+-- stop nagging and count the reminder as missed after this long
+ALTER TABLE settings ADD COLUMN IF NOT EXISTS nag_give_up_secs INT NOT NULL DEFAULT 1800;
@@ -0,0 +1,4 @@
+-- status can also be skipped now, which doesn't count for or against streaks
+
+-- a snoozed occurrence stays pending but isn't nagged about until then
+ALTER TABLE occurrences ADD COLUMN IF NOT EXISTS snoozed_until TIMESTAMPTZ;
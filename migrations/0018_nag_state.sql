@@ -0,0 +1,2 @@
+-- set while the bot is nagging about a pending occurrence, so the nag survives a restart
+ALTER TABLE occurrences ADD COLUMN IF NOT EXISTS nag_started_at TIMESTAMPTZ;
@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS buddies (
+    id              BIGSERIAL PRIMARY KEY,
+    user_id         TEXT NOT NULL,
+    -- gets a dm when the user leaves a reminder unacknowledged
+    buddy_id        TEXT NOT NULL,
+    -- NULL for every task of the user without a buddy of its own
+    task_id         BIGINT REFERENCES tasks(id) ON DELETE CASCADE,
+    after_secs      INT NOT NULL,
+    -- one of: asked, accepted, declined
+    status          TEXT NOT NULL,
+    UNIQUE NULLS NOT DISTINCT (user_id, task_id)
+);
+
+-- outlives the buddies row, so removing and asking again can't be used to spam someone
+CREATE TABLE IF NOT EXISTS buddy_asks (
+    user_id         TEXT NOT NULL,
+    buddy_id        TEXT NOT NULL,
+    last_asked_at   TIMESTAMPTZ NOT NULL,
+    -- they aren't asked by the same user again
+    declined        BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (user_id, buddy_id)
+);
+
+-- the buddy is only told once per occurrence
+ALTER TABLE occurrences ADD COLUMN IF NOT EXISTS buddy_told_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+-- one of: math, code. NULL if the ack phrase is enough to stop the nag
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS challenge TEXT;
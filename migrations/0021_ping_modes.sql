@@ -0,0 +1,19 @@
+-- one of: ghost (deleted right away), persistent (left in the chat),
+-- counter (one message edited with the number of pings)
+ALTER TABLE settings ADD COLUMN IF NOT EXISTS ping_mode TEXT NOT NULL DEFAULT 'ghost';
+
+-- pings rotate through these, {title} is replaced with the reminder's title
+ALTER TABLE settings ADD COLUMN IF NOT EXISTS nag_lines TEXT[] NOT NULL DEFAULT '{"hey buddy"}';
+
+-- carry over ghost_ping and nag_text, guarded so this can run again
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM information_schema.columns WHERE table_name = 'settings' AND column_name = 'ghost_ping') THEN
+        UPDATE settings SET ping_mode = CASE WHEN ghost_ping THEN 'ghost' ELSE 'persistent' END;
+        ALTER TABLE settings DROP COLUMN ghost_ping;
+    END IF;
+    IF EXISTS (SELECT 1 FROM information_schema.columns WHERE table_name = 'settings' AND column_name = 'nag_text') THEN
+        UPDATE settings SET nag_lines = ARRAY[nag_text];
+        ALTER TABLE settings DROP COLUMN nag_text;
+    END IF;
+END $$;
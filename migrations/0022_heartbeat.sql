@@ -0,0 +1,5 @@
+-- when the bot was last known to be running, to find the reminders it slept through
+CREATE TABLE IF NOT EXISTS heartbeat (
+    id          BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    last_seen   TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,111 @@
+use itertools::Itertools;
+use levenshtein::levenshtein;
+
+/// Lowercase, single spaces and no punctuation at the ends, so `OK!` matches `ok`.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .join(" ")
+        .trim_matches(|c: char| c.is_ascii_punctuation())
+        .to_lowercase()
+}
+
+/// How many typos are let through, a few more for longer phrases.
+fn allowed_distance(phrase: &str) -> usize {
+    1 + phrase.chars().count() / 5
+}
+
+/// Whether the message is close enough to the ack phrase to stop a nag.
+pub fn matches(message: &str, phrase: &str) -> bool {
+    let (message, phrase) = (normalize(message), normalize(phrase));
+    levenshtein(&message, &phrase) <= allowed_distance(&phrase)
+}
+
+/// Which of the pending reminders an ack is for.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AckTarget {
+    /// Just the phrase, fine as long as only one reminder is pending
+    Only,
+    /// `phrase 2`, counting from 1 like the list the bot shows
+    Number(usize),
+    /// `phrase all`
+    All,
+}
+
+/// None if the message isn't an ack at all.
+pub fn parse(message: &str, phrase: &str) -> Option<AckTarget> {
+    let message = normalize(message);
+    if message == normalize(phrase) {
+        return Some(AckTarget::Only);
+    }
+
+    // before the fuzzy match, which would take `i did it 2` as a typo of `i did it`
+    if let Some((rest, last)) = message.rsplit_once(' ') {
+        let target = match last {
+            "all" => Some(AckTarget::All),
+            n => n.parse().ok().map(AckTarget::Number)
+        };
+        if target.is_some() && matches(rest, phrase) {
+            return target;
+        }
+    }
+
+    matches(&message, phrase).then_some(AckTarget::Only)
+}
+
+/// Cleans up a new ack phrase, or says what's wrong with it.
+pub fn parse_phrase(text: &str) -> Result<String, String> {
+    const MAX_LEN: usize = 100;
+    let phrase = normalize(text);
+    if phrase.is_empty() {
+        return Err("the phrase needs some letters in it".into());
+    }
+    if phrase.chars().count() > MAX_LEN {
+        return Err(format!("the phrase can be at most {MAX_LEN} characters"));
+    }
+    Ok(phrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_case_spacing_and_end_punctuation() {
+        assert!(matches("OK!", "ok"));
+        assert!(matches("  i   did   it.  ", "I did it"));
+    }
+
+    #[test]
+    fn lets_typos_through_by_length() {
+        assert!(matches("dne", "done"));
+        assert!(!matches("nope", "done"));
+        assert!(matches("i dud itt", "i did it"));
+        assert!(!matches("hello there", "i did it"));
+    }
+
+    #[test]
+    fn picks_which_reminder_to_ack() {
+        assert_eq!(parse("done", "done"), Some(AckTarget::Only));
+        assert_eq!(parse("Done 2", "done"), Some(AckTarget::Number(2)));
+        assert_eq!(parse("done all!", "done"), Some(AckTarget::All));
+        assert_eq!(parse("done soon", "done"), None);
+        assert_eq!(parse("later 2", "done"), None);
+    }
+
+    #[test]
+    fn numbers_long_phrases_too() {
+        assert_eq!(parse("I did it 2", "i did it"), Some(AckTarget::Number(2)));
+        assert_eq!(parse("i dud it all", "i did it"), Some(AckTarget::All));
+        assert_eq!(parse("i did itt", "i did it"), Some(AckTarget::Only));
+        // a phrase ending in a number is still the phrase
+        assert_eq!(parse("take 2", "take 2"), Some(AckTarget::Only));
+        assert_eq!(parse("take 2 1", "take 2"), Some(AckTarget::Number(1)));
+    }
+
+    #[test]
+    fn cleans_up_new_phrases() {
+        assert_eq!(parse_phrase("  Got It! "), Ok("got it".into()));
+        assert!(parse_phrase("?!").is_err());
+        assert!(parse_phrase(&"a".repeat(101)).is_err());
+    }
+}
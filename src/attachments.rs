@@ -0,0 +1,53 @@
+use serenity::all::{Attachment as DiscordAttachment, CreateAttachment, CreateEmbed, CreateMessage};
+
+use crate::model::Attachment;
+
+/// Every file gets stored in the db, so keep them reasonably small.
+const MAX_FILE_SIZE: u32 = 8 * 1024 * 1024;
+/// Discord allows at most 10 files on a message.
+const MAX_FILES: usize = 10;
+
+/// Downloads the files of the message that creates a task.
+/// Errors are meant to be shown to the user.
+pub async fn download(attachments: &[DiscordAttachment]) -> Result<Vec<Attachment>, String> {
+    if attachments.len() > MAX_FILES {
+        return Err(format!("that's too many files, reminders can keep at most {MAX_FILES}"));
+    }
+
+    let mut files = Vec::with_capacity(attachments.len());
+    for a in attachments {
+        if a.size > MAX_FILE_SIZE {
+            return Err(format!("`{}` is too big, files can be at most {}MB", a.filename, MAX_FILE_SIZE / 1024 / 1024));
+        }
+        let data = a.download().await
+            .map_err(|e| format!("couldn't download `{}`: {e}", a.filename))?;
+        files.push(Attachment {
+            filename: a.filename.clone(),
+            content_type: a.content_type.clone(),
+            data
+        });
+    }
+
+    Ok(files)
+}
+
+/// Adds the files to the message, the first image is shown in the embed instead of below it.
+pub fn attach(mut message: CreateMessage, mut embed: CreateEmbed, files: Vec<Attachment>) -> CreateMessage {
+    let mut image_set = false;
+    for (i, file) in files.into_iter().enumerate() {
+        // `attachment://` only works with plain names, and they have to be unique
+        let filename = format!("{i}_{}", sanitize(&file.filename));
+        if !image_set && file.is_image() {
+            embed = embed.image(format!("attachment://{filename}"));
+            image_set = true;
+        }
+        message = message.add_file(CreateAttachment::bytes(file.data, filename));
+    }
+    message.embed(embed)
+}
+
+fn sanitize(filename: &str) -> String {
+    filename.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '_' })
+        .collect()
+}
@@ -1,21 +1,29 @@
 use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
 use chrono::Local;
+use itertools::Itertools;
 use ::time::macros::{format_description, offset};
-use ::time::UtcOffset;
+use ::time::{OffsetDateTime, Time, UtcOffset};
+use crate::{ack::{self, AckTarget}, attachments, catch_up};
+use crate::commands::{join_capped, task_line, Command};
+use crate::challenge::Challenge;
+use crate::components::{ComponentId, OccurrenceAction};
+use crate::embed_style::EmbedStyle;
 use crate::database::Database;
-use crate::jobs::{next_occurrence_time, EmbedReminderJob, SpamPingJob, SpamPingSignal, SpamPingStatus};
-use crate::model::{Task, TaskCreateInfo, TaskRemindInfo};
+use crate::jobs::{deliver, next_occurrence_time, reminder_components, reminder_embed, start_nag, watch_for_buddies, EmbedReminderJob, NagJob, SNOOZE};
+use crate::outbox::{Lane, Outbox, OutboxWorker};
+use crate::model::{parse_tags, Action, OccurrenceStatus, Priority, Task, TaskCreateInfo, TaskKind, TaskRemindInfo, UserSettings};
 use crate::scheduler::{TaskScheduler};
-use crate::time_parse::{TaskTimeInfo};
-use serenity::all::{Channel, ChannelId, Colour, CreateEmbed, CreateMessage, Http, Mention, MessageBuilder, ReactionType, Ready, UserId};
+use crate::time_parse::{format_duration, ChainTimeInfo, TaskTimeInfo};
+use serenity::all::{Channel, ChannelId, Colour, ComponentInteraction, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, Http, Mention, MessageBuilder, Interaction, RatelimitInfo, ReactionType, Ready, UserId};
 use serenity::{async_trait, json::json};
 use serenity::model::channel::Message;
 use serenity::prelude::*;
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use tokio::sync::{oneshot, watch};
-use tokio::task::JoinHandle;
+use tokio::task::{JoinHandle, JoinSet};
 use tokio::time;
 
 const HELP_STR: &str = "
@@ -34,31 +42,111 @@ VALID TIME EXAMPLES:
 9:30am umtwrfs rep
 10pm mwf
 10pm 1/29
+9am mwf rep pre 15m,1h (heads-up 15m and 1h before)
+10pm mwf silent (no notification, or `ping` to ping once, `nag` by default)
+7am a math (stop the nag by solving a sum, or `code` to retype a code)
+deadline 11/30 5pm (reminds 7d, 1d and 3h before by default)
+after 12 45m (45m after reminder 12 is done, see `tasks` for ids)
+
+SEVERAL AT ONCE:
+separate reminders with `---` or a blank line after the time
 
 VALID DATES:
 month/day
 td = today
 tmr = tommorow
+
+BUTTONS:
+Done marks the reminder done, Snooze sends it again in 10 minutes,
+Skip drops it without breaking your streak
+
+CHECKLISTS:
+info lines like `- [ ] pack charger` get a button each,
+the reminder is only done once every item is ticked
+
+LOOKS:
+info lines like `room: B204` become fields on the reminder,
+`colour: #ff8800` (or orange), `url: https://...` and `thumbnail: https://...` style it
+
+ATTACHMENTS:
+send images or files with the reminder (up to 10, 8MB each),
+they come back when it fires
+
+TAGS:
+put #work or #health anywhere in the title or info
+
+COMMANDS:
+stats - streaks and completion rates of recurring reminders
+tasks [ids or #tags] - list reminders, all of them by default
+pause <ids or #tags>
+resume <ids or #tags>
+delete <ids or #tags>
+edit <id> - with the new reminder on the next lines
+undo - reverse your last create, delete, edit, pause or resume
+archive - finished and deleted reminders
+archive restore <id>
+deadline offsets [7d,1d,3h] - show or change when deadlines remind you
+phrase [new phrase] - show or change what you say to stop the pings, `ok` by default
+(with several reminders pending, add which one: `ok 2` or `ok all`)
+nag [start 1m | every 5s | limit 30m | text <lines> | mode <mode>] - show or change how you get nagged
+(`nag text get up | {title}, now!` takes turns between lines, {title} is the reminder's title,
+`nag mode` is ghost, persistent or counter)
+
+BUDDIES:
+buddy - show your buddies
+buddy @someone [id] [after] - ask someone to get a DM when you leave reminder `id` (or any reminder) unchecked, after 30m by default
+buddy remove [id]
+
+TEMPLATES:
+template save <name> - with a reminder on the next lines
+template list
+template delete <name>
+t <name> [time] - create a reminder from a template, optionally at a different time
 ";
 
 pub struct DzContextInner {
     pub db: Arc<Database>,
-    /// sometime clean it up or remove it when the last reminder job is gone
-    /// maybe..
-    pub spammer_ctl: HashMap<UserId, SpamPingJob>,
+    /// map of the occurrence ID to the nag about it
+    pub nags_ctl: HashMap<i64, NagJob>,
     /// map of the reminder task ID in the database to the job
     pub reminders_ctl: HashMap<i64, EmbedReminderJob>,
+    pub outbox: Outbox,
+    /// Set once the bot is shutting down, commands and buttons are turned away from then on
+    pub shutting_down: bool,
+    /// Snooze timers, buddy watches and the heartbeat, aborted on shutdown
+    background: JoinSet<()>,
 }
 
 pub type DzContext = Arc<RwLock<DzContextInner>>;
 
+const SHUTTING_DOWN_REPLY: &str = "restarting, try again in a minute";
+
+/// How long shutting down waits for queued messages to go out.
+const SHUTDOWN_DRAIN: Duration = Duration::from_secs(20);
+
+/// Opening a DM fails for good when the user blocked the bot or left every shared server.
+const DM_CHANNEL_ATTEMPTS: u64 = 5;
+
 pub struct DZBot {
-    db: Arc<Database>,
-    scheduler: TaskScheduler,
-    ctx: DzContext
+    pub db: Arc<Database>,
+    pub scheduler: TaskScheduler,
+    pub ctx: DzContext,
+    /// Taken and started once the bot is connected, deliveries queue up until then
+    outbox_worker: Mutex<Option<OutboxWorker>>,
 }
 
 impl DzContextInner {
+    /// Runs a task that only lives as long as the bot does.
+    pub fn spawn_background(&mut self, task: impl Future<Output = ()> + Send + 'static) {
+        // too late, whatever it was for is saved and starts again with the bot
+        if self.shutting_down {
+            return;
+        }
+        // finished ones would pile up otherwise
+        while self.background.try_join_next().is_some() {}
+        self.background.spawn(task);
+    }
+
     /// Attempts to retrieve a channel from the database, otherwise uses discord's api
     pub async fn get_dm_channel(&self, http: Arc<Http>, uid: UserId) -> Result<ChannelId> {
         let ch_fetch = self.db.dm_channel(&uid)
@@ -66,20 +154,20 @@ impl DzContextInner {
         Ok(
             match ch_fetch {
                 None => {
-                    let mut channel = 
-                        uid.create_dm_channel(http.clone())
-                        .await;
-                    
-                    while let Err(e) = channel {
-                        eprintln!("Error fetching channel: {e}.");
-                        let retry_time = 300;
-                        eprintln!("Trying to refetch in {retry_time}ms..");
-                        time::sleep(Duration::from_millis(retry_time)).await;
-                        channel = uid.create_dm_channel(http.clone())
-                            .await;
-                    }
-                    
-                    let channel = channel.unwrap().id;
+                    let mut attempt = 1;
+                    let channel = loop {
+                        match uid.create_dm_channel(http.clone()).await {
+                            Ok(channel) => break channel.id,
+                            Err(e) if attempt < DM_CHANNEL_ATTEMPTS => {
+                                eprintln!("Error fetching channel: {e}.");
+                                let retry_time = 300 * attempt;
+                                eprintln!("Trying to refetch in {retry_time}ms..");
+                                time::sleep(Duration::from_millis(retry_time)).await;
+                                attempt += 1;
+                            }
+                            Err(e) => return Err(e).context(format!("Couldn't open a DM with {uid}")),
+                        }
+                    };
                     self.db.put_dm_channel(&uid, &channel).await?;
                     channel
                 }
@@ -88,6 +176,34 @@ impl DzContextInner {
         )
     }
 
+    /// Stops the pings about an occurrence for good, it won't be picked up after a restart either.
+    pub async fn stop_nag(&mut self, occurrence_id: i64) {
+        self.nags_ctl.remove(&occurrence_id);
+        if let Err(e) = self.db.set_nag_started(occurrence_id, None).await {
+            eprintln!("Failed to clear nag of occurrence {occurrence_id}: {e}");
+        }
+    }
+
+    /// Stops the pings about any occurrence of a task.
+    pub async fn stop_nags_of_task(&mut self, task_id: i64) {
+        let occurrence_ids = self.nags_ctl.iter()
+            .filter(|(_, nag)| nag.task_id == task_id)
+            .map(|(id, _)| *id)
+            .collect_vec();
+        for occurrence_id in occurrence_ids {
+            self.stop_nag(occurrence_id).await;
+        }
+    }
+
+    /// (occurrence id, title, challenge) of what the user is being nagged about, oldest first.
+    pub fn nags_for(&self, uid: &UserId) -> Vec<(i64, String, Option<Challenge>)> {
+        self.nags_ctl.iter()
+            .filter(|(_, nag)| nag.user_id == *uid)
+            .map(|(id, nag)| (*id, nag.title.clone(), nag.challenge.clone()))
+            .sorted_by_key(|(id, ..)| *id)
+            .collect()
+    }
+
     /// Kills the job.  
     pub fn kill_reminder_job(&self, task_id: i64) -> bool {
         let job = self.reminders_ctl.get(&task_id);
@@ -100,43 +216,189 @@ impl DzContextInner {
         }
         false
     }
+
+    /// Stops the job without deleting the task.
+    pub fn pause_reminder_job(&self, task_id: i64) -> bool {
+        let job = self.reminders_ctl.get(&task_id);
+        if let Some(job) = job {
+            if let Err(e) = job.pause() {
+                eprintln!("Error pausing embed reminder job: {e}");
+                return false;
+            }
+            return true;
+        }
+        false
+    }
 }
 
 impl DZBot {
     pub fn new(db: Arc<Database>) -> Self {
+        let (outbox, outbox_worker) = Outbox::new();
         let ctx = Arc::new(RwLock::new(
             DzContextInner {
                 db: db.clone(),
-                spammer_ctl: HashMap::new(),
-                reminders_ctl: HashMap::new()
+                nags_ctl: HashMap::new(),
+                reminders_ctl: HashMap::new(),
+                outbox,
+                shutting_down: false,
+                background: JoinSet::new()
             }
         ));
         Self {
             scheduler: TaskScheduler::new(ctx.clone()),
             ctx,
             db,
+            outbox_worker: Mutex::new(Some(outbox_worker)),
         }
     }
 }
 
-fn parse_text(content: &String) -> Result<TaskCreateInfo, String> {
+/// Stops every job and waits for the messages already queued, then closes the database.
+/// Nags and snoozes are left as they are in the database, they pick up again on the next start.
+pub async fn shut_down(ctx: &DzContext) {
+    let (mut background, db) = {
+        let mut ctx = ctx.write().await;
+        ctx.shutting_down = true;
+        (std::mem::take(&mut ctx.background), ctx.db.clone())
+    };
+    // the heartbeat among them, it can't move last_seen past the jobs stopping
+    background.shutdown().await;
+
+    // catching up on the next start begins from here, so anything due from now on is reported then
+    if let Err(e) = db.beat().await {
+        eprintln!("Failed to record heartbeat: {e}");
+    }
+
+    let outbox = {
+        let mut ctx = ctx.write().await;
+        // dropping the jobs stops them without touching the database
+        ctx.nags_ctl.clear();
+        ctx.reminders_ctl.clear();
+        ctx.outbox.clone()
+    };
+
+    println!("waiting for queued messages...");
+    if !outbox.drain(SHUTDOWN_DRAIN).await {
+        eprintln!("Gave up on queued messages after {}s", SHUTDOWN_DRAIN.as_secs());
+    }
+
+    db.close().await;
+    println!("database closed");
+}
+
+/// What a message with several reminders ended up creating.
+fn bulk_summary(created: Vec<String>, errors: Vec<String>, total: usize) -> String {
+    let mut lines = vec![format!("created **{}** of **{total}** reminders", created.len())];
+    lines.extend(created);
+    if !errors.is_empty() {
+        lines.push("\nproblems:".into());
+        lines.extend(errors);
+    }
+    join_capped(lines, "\n")
+}
+
+fn parse_text(content: &str) -> Result<TaskCreateInfo, String> {
     let mut lines = content.lines();
     let title = lines.next().ok_or(String::from("no title?"))?.to_string();
     let times_str = lines.next_back().ok_or(String::from("no times?"))?.to_string();
-    let time_info = TaskTimeInfo::parse(&times_str)?;
     let info: String = lines.collect::<Vec<_>>().join("\n");
+    let tags = parse_tags(&format!("{title}\n{info}"));
+    let (style, info) = EmbedStyle::extract(&info)?;
+    style.validate(&title, &info)?;
+
+    if is_chain_line(&times_str) {
+        let chain_info = ChainTimeInfo::parse(&times_str)?;
+        return Ok(
+            TaskCreateInfo {
+                kind: TaskKind::Chained,
+                title,
+                info,
+                // not used, the time comes from when the other task is done
+                remind_at: Time::MIDNIGHT,
+                on_days: None,
+                repeat_weekly: false,
+                date: None,
+                lead_times: chain_info.lead_times,
+                priority: chain_info.priority,
+                challenge: chain_info.challenge,
+                tags,
+                offsets: vec![],
+                after_task: Some(chain_info.after),
+                delay: chain_info.delay,
+                attachments: vec![],
+                style
+            }
+        );
+    }
+
+    let time_info = TaskTimeInfo::parse(&times_str)?;
+    let kind = if time_info.deadline {
+        TaskKind::Deadline
+    } else if time_info.date.is_some() {
+        TaskKind::Once
+    } else {
+        TaskKind::Recurring
+    };
     Ok(
         TaskCreateInfo { 
+            kind,
             title, 
             info, 
             remind_at: time_info.remind_time, 
             on_days: time_info.days_of_week, 
             repeat_weekly: time_info.repeat_weekly,
-            date: time_info.date 
+            date: time_info.date,
+            lead_times: time_info.lead_times,
+            priority: time_info.priority,
+            challenge: time_info.challenge,
+            tags,
+            // filled in from the user's settings
+            offsets: vec![],
+            after_task: None,
+            delay: ::time::Duration::ZERO,
+            attachments: vec![],
+            style
         }
     )
 }
 
+fn is_chain_line(line: &str) -> bool {
+    line.split_whitespace().next().is_some_and(|t| t.eq_ignore_ascii_case("after"))
+}
+
+/// Splits a message into separate tasks.
+/// `---` always ends a task, a blank line only does right after a time line,
+/// so blank lines inside the info still work.
+fn split_blocks(content: &str) -> Vec<String> {
+    let mut blocks = vec![];
+    let mut current: Vec<&str> = vec![];
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        let ends_block = trimmed == "---" || (
+            trimmed.is_empty()
+            && current.len() >= 2
+            && current.last().is_some_and(|l| {
+                TaskTimeInfo::parse(l.trim()).is_ok() || ChainTimeInfo::parse(l.trim()).is_ok()
+            })
+        );
+
+        if ends_block {
+            if !current.is_empty() {
+                blocks.push(current.join("\n"));
+            }
+            current.clear();
+        } else if !(trimmed.is_empty() && current.is_empty()) {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current.join("\n"));
+    }
+
+    blocks
+}
+
 async fn report_err(channel: ChannelId, http: Arc<Http>, err: impl ToString + Into<String>) {
     let res = channel.send_message(
         http, 
@@ -148,7 +410,7 @@ async fn report_err(channel: ChannelId, http: Arc<Http>, err: impl ToString + In
     }
 }
 
-fn reply_string(task: &Task) -> String {
+pub fn reply_string(task: &Task) -> String {
     if let Some(dt_utc) = next_occurrence_time(&task) {
         let offset_sec = Local::now()
             .offset()
@@ -156,7 +418,7 @@ fn reply_string(task: &Task) -> String {
         let local_offset = UtcOffset::from_whole_seconds(offset_sec)
             .expect("??");
         let dt_local = dt_utc.to_offset(local_offset);
-        format!(
+        let mut reply = format!(
             "ok\nreminding on {}",
             if let Some(days) = task.on_days() {
                 // TODO! neatly lay out in order
@@ -178,25 +440,495 @@ fn reply_string(task: &Task) -> String {
                         .format(format_description!("`[year]/[month]/[day]` at `[hour repr:12]:[minute] [period]`")).unwrap(), 
                 )
             },
+        );
+        if let (TaskKind::Deadline, Some(deadline)) = (task.kind(), task.datetime_utc()) {
+            let offsets = task.deadline_offsets();
+            reply.push_str(&format!("\ndeadline **<t:{}:f>**", deadline.unix_timestamp()));
+            if !offsets.is_empty() {
+                reply.push_str(&format!(
+                    ", reminding **{}** before",
+                    offsets.into_iter().map(format_duration).join(", ")
+                ));
+            }
+        }
+        reply.push_str(&priority_and_leads(task));
+        reply
+    } else if let Some((after, delay)) = task.chained_after() {
+        format!(
+            "ok\nreminding **{}** after `{after}` is done{}", 
+            format_duration(delay),
+            priority_and_leads(task)
         )
     } else {
         "THIS REMINDER WILL NEVER RUN.".into()
     }
 }
 
+fn priority_and_leads(task: &Task) -> String {
+    let mut lines = String::new();
+    let priority = task.remind_info().priority;
+    if priority != Priority::Nag {
+        lines.push_str(&format!("\npriority: **{}**", priority.as_str()));
+    }
+    if let Some(challenge) = task.remind_info().challenge {
+        lines.push_str(&format!("\nchallenge to stop the nag: **{}**", challenge.as_str()));
+    }
+    let leads = task.lead_times();
+    if !leads.is_empty() {
+        lines.push_str(&format!(
+            "\nheads-up **{}** before",
+            leads.into_iter().map(format_duration).join(", ")
+        ));
+    }
+    lines
+}
+
+impl DZBot {
+    /// Parses and validates a task, filling in whatever comes from the user's settings.
+    pub async fn parse_task(&self, user_id: &UserId, content: &str) -> Result<TaskCreateInfo, String> {
+        let mut create_info = parse_text(content)?;
+
+        if let Some(after) = create_info.after_task {
+            match self.db.tasks_for(user_id).await {
+                Ok(tasks) if tasks.iter().any(|t| t.id() == after) => {},
+                Ok(_) => return Err(format!("you don't have a reminder `{after}` to chain after")),
+                Err(e) => return Err(format!("couldn't check the reminder to chain after: {e}"))
+            }
+        } else if create_info.on_days.is_none() && create_info.date.is_none() {
+            return Err("bro gave me no info".into());
+        }
+
+        if create_info.kind == TaskKind::Deadline {
+            create_info.offsets = match self.db.settings(user_id).await {
+                Ok(settings) => settings.deadline_offsets,
+                Err(e) => {
+                    eprintln!("Failed to fetch settings: {e}");
+                    UserSettings::default().deadline_offsets
+                }
+            };
+        }
+
+        Ok(create_info)
+    }
+
+    /// Saves the task to the database and starts its reminder job.
+    pub async fn add_task(&self, ctx: &Context, user_id: &UserId, create_info: &TaskCreateInfo) -> Result<Task> {
+        let task = self.db.add_task(user_id, create_info).await
+            .context("Failed to save task to db")?;
+        self.scheduler.add_task(ctx.http.clone(), &task).await
+            .context("Failed to schedule task")?;
+        self.log_action(user_id, Action::Create, &[task.id()]).await;
+        println!("success");
+        Ok(task)
+    }
+
+    /// Logs the action so it can be undone. Not being able to undo
+    /// shouldn't stop the action itself, so errors are only printed.
+    pub async fn log_action(&self, user_id: &UserId, action: Action, task_ids: &[i64]) {
+        if let Err(e) = self.db.log_action(user_id, action, task_ids).await {
+            eprintln!("Failed to log {} for {user_id}: {e}", action.as_str());
+        }
+    }
+
+    /// Schedules the tasks chained after the ones that just got done.
+    async fn arm_chains(&self, ctx: &Context, task_ids: &[i64]) {
+        let chained = match self.db.arm_chains(task_ids).await {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Failed to set off chained tasks: {e}");
+                return;
+            }
+        };
+        for task in chained {
+            if let Err(e) = self.reschedule(ctx, &task).await {
+                eprintln!("Failed to schedule chained task {}: {e}", task.id());
+            }
+        }
+    }
+
+    /// Replaces the task's reminder job after it has changed in the db.
+    /// Paused tasks just get their job stopped.
+    pub async fn reschedule(&self, ctx: &Context, task: &Task) -> Result<()> {
+        self.ctx.read().await.pause_reminder_job(task.id());
+        if !task.paused() {
+            self.scheduler.add_task(ctx.http.clone(), task).await?;
+        }
+        Ok(())
+    }
+
+    /// Creates every valid task in one go.
+    /// Returns a summary with what was created and what went wrong.
+    pub async fn add_tasks_bulk(&self, ctx: &Context, user_id: &UserId, blocks: &[String]) -> String {
+        let mut create_infos = vec![];
+        let mut errors = vec![];
+
+        for (i, block) in blocks.iter().enumerate() {
+            match self.parse_task(user_id, block).await {
+                Ok(c) => create_infos.push(c),
+                Err(e) => {
+                    let first_line = block.lines().next().unwrap_or_default();
+                    errors.push(format!("#{} (`{first_line}`): {e}", i + 1));
+                }
+            }
+        }
+
+        // every block failed, don't log an empty create
+        if create_infos.is_empty() {
+            return bulk_summary(vec![], errors, blocks.len());
+        }
+
+        let tasks = match self.db.add_tasks(user_id, &create_infos).await {
+            Ok(t) => t,
+            Err(e) => return format!("Failed to save tasks to db, nothing was created: {e}")
+        };
+
+        let ids = tasks.iter().map(Task::id).collect::<Vec<_>>();
+        self.log_action(user_id, Action::Create, &ids).await;
+
+        let mut created = vec![];
+        for task in &tasks {
+            match self.scheduler.add_task(ctx.http.clone(), task).await {
+                Ok(_) => created.push(task_line(task)),
+                Err(e) => errors.push(format!("`{}` was saved but failed to schedule: {e}", task.id()))
+            }
+        }
+
+        bulk_summary(created, errors, blocks.len())
+    }
+
+    /// Ticks or unticks a checklist item and updates the reminder embed.
+    /// The occurrence is done once every item is ticked.
+    async fn check_item(
+        &self, 
+        ctx: &Context, 
+        component: &ComponentInteraction, 
+        occurrence_id: i64, 
+        item: i32
+    ) -> Result<()> {
+        let occurrence = self.db.occurrence(occurrence_id).await?;
+        if occurrence.status != OccurrenceStatus::Pending {
+            let reply = format!("this reminder is already {}", occurrence.status.as_str());
+            return respond_ephemeral(ctx, component, reply).await;
+        }
+
+        let task_info = self.db.task(occurrence.task_id).await?.remind_info();
+        let checked = self.db.toggle_checklist_item(occurrence_id, item).await?;
+        let finished = checked.len() as i32 >= occurrence.checklist_len;
+
+        let outcome = if finished {
+            let task_id = self.db.complete_occurrence(occurrence_id).await?;
+            self.arm_chains(ctx, &[task_id]).await;
+            // a challenge still has to be answered to stop the pings
+            let challenged = self.ctx.read().await.nags_ctl.get(&occurrence_id)
+                .is_some_and(|nag| nag.challenge.is_some());
+            if !challenged {
+                self.ctx.write().await.stop_nag(occurrence_id).await;
+            }
+            Some("✅ **done**".to_string())
+        } else {
+            None
+        };
+
+        component.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(updated_embed(component, &task_info, &checked, outcome))
+                .components(reminder_components(occurrence_id, &task_info.info, &checked, finished))
+        )).await?;
+
+        Ok(())
+    }
+
+    /// Handles the done/snooze/skip buttons under a reminder.
+    /// The buttons are disabled afterwards, a snoozed reminder comes back as a new message.
+    async fn resolve_occurrence(
+        &self, 
+        ctx: &Context, 
+        component: &ComponentInteraction, 
+        occurrence_id: i64, 
+        action: OccurrenceAction
+    ) -> Result<()> {
+        let occurrence = self.db.occurrence(occurrence_id).await?;
+        if occurrence.status != OccurrenceStatus::Pending {
+            let reply = format!("this reminder is already {}", occurrence.status.as_str());
+            return respond_ephemeral(ctx, component, reply).await;
+        }
+
+        // snoozing is fine, but done and skip can't get around the challenge
+        let challenge = self.ctx.read().await.nags_ctl.get(&occurrence_id)
+            .and_then(|nag| nag.challenge.clone());
+        if let (Some(challenge), OccurrenceAction::Done | OccurrenceAction::Skip) = (challenge, action) {
+            return respond_ephemeral(ctx, component, challenge.prompt()).await;
+        }
+
+        // same as acking, a checklist is only done once every item is ticked
+        let checked = self.db.checked_items(occurrence_id).await?;
+        let unticked = occurrence.checklist_len - checked.len() as i32;
+        if action == OccurrenceAction::Done && unticked > 0 {
+            let reply = format!("tick everything on the checklist first, {unticked} left");
+            return respond_ephemeral(ctx, component, reply).await;
+        }
+
+        let task = self.db.task(occurrence.task_id).await?;
+        let task_info = task.remind_info();
+
+        let outcome = match action {
+            OccurrenceAction::Done => {
+                let task_id = self.db.complete_occurrence(occurrence_id).await?;
+                self.arm_chains(ctx, &[task_id]).await;
+                "✅ **done**".to_string()
+            }
+            OccurrenceAction::Skip => {
+                self.db.skip_occurrence(occurrence_id).await?;
+                "⏭️ **skipped**".to_string()
+            }
+            OccurrenceAction::Snooze => {
+                let until = OffsetDateTime::now_utc() + SNOOZE;
+                self.db.snooze_occurrence(occurrence_id, until).await?;
+                self.redeliver_after_snooze(ctx.http.clone(), task.id(), task_info.clone(), occurrence_id, until).await;
+                format!("💤 **snoozed** until <t:{}:t>", until.unix_timestamp())
+            }
+        };
+        // a snoozed reminder starts nagging again when it comes back
+        self.ctx.write().await.stop_nag(occurrence_id).await;
+
+        component.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(updated_embed(component, &task_info, &checked, Some(outcome)))
+                .components(reminder_components(occurrence_id, &task_info.info, &checked, true))
+        )).await?;
+
+        Ok(())
+    }
+
+    /// Sends the reminder again once the snooze is over, unless it was dealt with in the meantime.
+    async fn redeliver_after_snooze(
+        &self, 
+        http: Arc<Http>, 
+        task_id: i64, 
+        task_info: TaskRemindInfo, 
+        occurrence_id: i64, 
+        until: OffsetDateTime
+    ) {
+        let ctx = self.ctx.clone();
+        let db = self.db.clone();
+        self.ctx.write().await.spawn_background(async move {
+            let left = (until - OffsetDateTime::now_utc()).max(::time::Duration::ZERO);
+            tokio::time::sleep(std::time::Duration::from_secs(left.whole_seconds() as u64)).await;
+            match db.occurrence(occurrence_id).await {
+                Ok(occurrence) if occurrence.status == OccurrenceStatus::Pending => {}
+                Ok(_) => return,
+                Err(e) => {
+                    eprintln!("Failed to get snoozed occurrence {occurrence_id}: {e}");
+                    return;
+                }
+            }
+            if let Err(e) = db.end_snooze(occurrence_id).await {
+                eprintln!("Failed to end snooze of occurrence {occurrence_id}: {e}");
+            }
+            if let Err(e) = deliver(http, ctx, task_id, task_info, Some(occurrence_id), None).await {
+                eprintln!("Failed to send snoozed reminder for task {task_id}: {e}");
+            }
+        });
+    }
+
+    /// Sends a reminder from the missed-while-offline summary, as if it just came up.
+    async fn redeliver(&self, ctx: &Context, component: &ComponentInteraction, occurrence_id: i64) -> Result<()> {
+        let Some(occurrence) = self.db.reopen_occurrence(occurrence_id, &component.user.id).await? else {
+            return respond_ephemeral(ctx, component, "already sent that one".into()).await;
+        };
+
+        let task = self.db.task(occurrence.task_id).await?;
+        let task_info = task.remind_info();
+        respond_ephemeral(ctx, component, format!("here's **{}**", task_info.title)).await?;
+        deliver(ctx.http.clone(), self.ctx.clone(), task.id(), task_info, Some(occurrence.id), None).await
+    }
+
+    /// Handles the consent prompt, only the person who was asked can answer it.
+    /// The user who asked hears back either way.
+    async fn answer_buddy(
+        &self, 
+        ctx: &Context, 
+        component: &ComponentInteraction, 
+        buddy: i64, 
+        accept: bool
+    ) -> Result<()> {
+        let Some(buddy) = self.db.answer_buddy(buddy, &component.user.id, accept).await? else {
+            return respond_ephemeral(ctx, component, "this request isn't open anymore".into()).await;
+        };
+
+        let answer = if accept { "✅ you're their buddy now" } else { "❌ you said no" };
+        component.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(format!("{}\n\n{answer}", component.message.content))
+                .components(vec![])
+        )).await?;
+
+        let reply = format!(
+            "{} {} being your buddy for {}",
+            buddy.buddy_id.mention(),
+            if accept { "agreed to" } else { "said no to" },
+            buddy.scope()
+        );
+        let channel = self.ctx.read().await.get_dm_channel(ctx.http.clone(), buddy.user_id).await?;
+        let outbox = self.ctx.read().await.outbox.clone();
+        outbox.send(channel, CreateMessage::new().content(reply), Lane::First).await?;
+        Ok(())
+    }
+
+    /// Picks up the nags, snoozes and buddy checks that were running when the bot went down.
+    async fn resume_interrupted(&self, ctx: &Context) -> Result<()> {
+        let occurrences = self.db.interrupted_occurrences().await?;
+        println!("resuming {} nags and snoozes...", occurrences.len());
+
+        for occurrence in occurrences {
+            let task = self.db.task(occurrence.task_id).await?;
+            let task_info = task.remind_info();
+            if let Some(until) = occurrence.snoozed_until {
+                self.redeliver_after_snooze(ctx.http.clone(), task.id(), task_info, occurrence.id, until).await;
+            } else if let Some(started) = occurrence.nag_started_at {
+                start_nag(&self.ctx, ctx.http.clone(), occurrence.id, task.id(), &task_info, started).await?;
+            }
+        }
+        watch_for_buddies(&self.ctx, ctx.http.clone(), None).await
+    }
+
+    /// Stops the nags and marks the occurrences done.
+    /// Occurrences with a checklist stay pending, they are only done once every item is ticked.
+    async fn acknowledge(&self, ctx: &Context, occurrence_ids: &[i64]) {
+        let mut task_ids = vec![];
+        for &id in occurrence_ids {
+            self.ctx.write().await.stop_nag(id).await;
+            let res = match self.db.occurrence(id).await {
+                Ok(o) if o.status == OccurrenceStatus::Pending && o.checklist_len == 0 => 
+                    self.db.complete_occurrence(id).await.map(Some),
+                Ok(_) => Ok(None),
+                Err(e) => Err(e)
+            };
+            match res {
+                Ok(Some(task_id)) => task_ids.push(task_id),
+                Ok(None) => {},
+                Err(e) => eprintln!("Failed to complete occurrence {id}: {e}")
+            }
+        }
+        self.arm_chains(ctx, &task_ids).await;
+    }
+}
+
+/// The reminder embed with the stats footer and image from when it was sent.
+fn updated_embed(
+    component: &ComponentInteraction, 
+    task_info: &TaskRemindInfo, 
+    checked: &[i32], 
+    outcome: Option<String>
+) -> CreateEmbed {
+    let old_embed = component.message.embeds.first();
+    let footer = old_embed
+        .and_then(|e| e.footer.as_ref())
+        .map(|f| f.text.clone());
+    let mut embed = reminder_embed(task_info, checked, footer, outcome);
+    if let Some(image) = old_embed.and_then(|e| e.image.as_ref()) {
+        embed = embed.image(&image.url);
+    }
+    embed
+}
+
+async fn respond_ephemeral(ctx: &Context, component: &ComponentInteraction, content: String) -> Result<()> {
+    component.create_response(ctx, CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true)
+    )).await
+    .map_err(anyhow::Error::from)
+}
+
 #[async_trait]
 impl EventHandler for DZBot {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Component(component) = interaction else {
+            return;
+        };
+        let Some(id) = ComponentId::parse(&component.data.custom_id) else {
+            eprintln!("Unknown component id: {}", component.data.custom_id);
+            return;
+        };
+        if self.ctx.read().await.shutting_down {
+            if let Err(e) = respond_ephemeral(&ctx, &component, SHUTTING_DOWN_REPLY.into()).await {
+                eprintln!("Failed to turn away interaction: {e}");
+            }
+            return;
+        }
+
+        let res = match id {
+            ComponentId::CheckItem { occurrence_id, item } =>
+                self.check_item(&ctx, &component, occurrence_id, item).await,
+            ComponentId::Resolve { occurrence_id, action } =>
+                self.resolve_occurrence(&ctx, &component, occurrence_id, action).await,
+            ComponentId::AnswerBuddy { buddy, accept } =>
+                self.answer_buddy(&ctx, &component, buddy, accept).await,
+            ComponentId::Redeliver { occurrence_id } =>
+                self.redeliver(&ctx, &component, occurrence_id).await,
+        };
+
+        if let Err(e) = res {
+            eprintln!("Failed to handle interaction: {e}");
+        }
+    }
+
+    /// Serenity retries the request itself, the outbox holds off everything else for as long as Discord asked.
+    async fn ratelimit(&self, data: RatelimitInfo) {
+        eprintln!("Rate limited by Discord on {} for {:.1}s", data.path, data.timeout.as_secs_f32());
+        self.ctx.read().await.outbox.pause(data.timeout);
+    }
+
     async fn ready(&self, ctx: Context, _ready: Ready) {
         println!("bot started!");
 
-        let tasks = self.db.all_tasks().await.expect("Could not get all tasks");
+        // ready fires again on reconnects, the worker only starts the first time
+        let first_ready = match self.outbox_worker.lock().await.take() {
+            Some(worker) => {
+                tokio::spawn(worker.run(ctx.http.clone()));
+                true
+            }
+            None => false
+        };
+
+        let tasks = match self.db.all_tasks().await {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                eprintln!("Could not get all tasks: {e}");
+                return;
+            }
+        };
+
+        // after a reconnect the jobs kept running, only a restart misses anything
+        let mut missed = None;
+        if first_ready {
+            match catch_up::record_missed(&self.db, &tasks, OffsetDateTime::now_utc()).await {
+                Ok(m) => missed = m,
+                Err(e) => eprintln!("Failed to look for missed reminders: {e}")
+            }
+            self.ctx.write().await.spawn_background(catch_up::heartbeat(self.db.clone()));
+        }
         println!("found {} tasks.. rescheduling all...", tasks.len());
 
-        for task in tasks {
-            self.scheduler.add_task(ctx.http.clone(), &task).await.unwrap();
+        for task in tasks.iter().filter(|t| !t.paused()) {
+            if let Err(e) = self.scheduler.add_task(ctx.http.clone(), task).await {
+                eprintln!("Failed to reschedule task {}: {e}", task.id());
+            }
         }
 
         println!("finished rescheduling all tasks...");
+
+        // the nags, snoozes and buddy watches are still running after a reconnect
+        if first_ready {
+            if let Err(e) = self.resume_interrupted(&ctx).await {
+                eprintln!("Failed to resume nags: {e}");
+            }
+        }
+
+        if let Some(missed) = missed {
+            missed.send_summaries(&self.ctx, ctx.http.clone()).await;
+        }
     }
 
     async fn message(&self, ctx: Context, msg: Message) {
@@ -208,26 +940,135 @@ impl EventHandler for DZBot {
         if msg.guild_id.is_some() {
             return;
         }
+
+        if self.ctx.read().await.shutting_down {
+            if let Err(e) = msg.reply(&ctx, SHUTTING_DOWN_REPLY).await {
+                eprintln!("Failed to turn away message: {e}");
+            }
+            return;
+        }
         
         // Check if user is tryna stop a mass pinging
-        {
-            let uid = msg.author.id;
-            let dzctx = self.ctx.read().await;
-            let spam_job = dzctx.spammer_ctl
-                .get(&uid);
-
-            if let Some(s) = spam_job {
-                if s.status() == SpamPingStatus::Active {
-                    s.signal(SpamPingSignal::Stop);
-                    let _ = msg.react(ctx.http(), ReactionType::Unicode("👍".into())).await;
+        let uid = msg.author.id;
+        let nags = self.ctx.read().await.nags_for(&uid);
+        if !nags.is_empty() {
+            let phrase = match self.db.settings(&uid).await {
+                Ok(settings) => settings.ack_phrase,
+                Err(e) => {
+                    eprintln!("Failed to fetch settings: {e}");
+                    UserSettings::default().ack_phrase
+                }
+            };
+
+            // a right answer stops its own nag, no phrase needed
+            let answered = nags.iter()
+                .filter(|(_, _, challenge)| challenge.as_ref().is_some_and(|c| c.check(&msg.content)))
+                .map(|(id, ..)| *id)
+                .collect_vec();
+            if !answered.is_empty() {
+                self.acknowledge(&ctx, &answered).await;
+                let _ = msg.react(ctx.http(), ReactionType::Unicode("👍".into())).await;
+                return;
+            }
+
+            let acked = match ack::parse(&msg.content, &phrase) {
+                Some(AckTarget::Only) if nags.len() > 1 => {
+                    let mut lines = vec![format!("which one? say `{phrase} <number>` or `{phrase} all`")];
+                    lines.extend(nags.iter().enumerate().map(|(i, (_, title, _))| format!("{}. **{title}**", i + 1)));
+                    Err(lines.join("\n"))
+                }
+                Some(AckTarget::Only | AckTarget::All) => Ok(nags.iter().collect_vec()),
+                Some(AckTarget::Number(n)) => match n.checked_sub(1).and_then(|i| nags.get(i)) {
+                    Some(nag) => Ok(vec![nag]),
+                    None => Err(format!("there's no number {n}, pick one from 1 to {}", nags.len()))
+                },
+                None => Ok(vec![])
+            };
+            match acked {
+                Ok(acked) if !acked.is_empty() => {
+                    // the phrase can't get around a challenge
+                    let (challenged, ids): (Vec<_>, Vec<_>) = acked.into_iter()
+                        .partition(|(_, _, challenge)| challenge.is_some());
+                    if !ids.is_empty() {
+                        let ids = ids.into_iter().map(|(id, ..)| *id).collect_vec();
+                        self.acknowledge(&ctx, &ids).await;
+                        let _ = msg.react(ctx.http(), ReactionType::Unicode("👍".into())).await;
+                    }
+                    if !challenged.is_empty() {
+                        let reply = challenged.iter()
+                            .filter_map(|(_, title, challenge)| challenge.as_ref().map(|c| format!("**{title}**: {}", c.prompt())))
+                            .join("\n");
+                        if let Err(e) = msg.reply_ping(ctx, reply).await {
+                            eprintln!("{e}");
+                        }
+                    }
+                    return;
+                }
+                Ok(_) => {},
+                Err(reply) => {
+                    if let Err(e) = msg.reply_ping(ctx, reply).await {
+                        eprintln!("{e}");
+                    }
                     return;
                 }
-            } 
+            }
+
+            // commands and new reminders still work while nagging
+            let is_chat = Command::parse(&msg.content).is_none() && !msg.content.trim().contains('\n');
+            if is_chat {
+                let hint = if nags.iter().all(|(_, _, challenge)| challenge.is_some()) {
+                    "that's not it, try again".to_string()
+                } else {
+                    format!("that's not it, say `{phrase}` to stop the pings")
+                };
+                if let Err(e) = msg.reply_ping(ctx, hint).await {
+                    eprintln!("{e}");
+                }
+                return;
+            }
+        }
+
+        if let Some(cmd) = Command::parse(&msg.content) {
+            let reply = match cmd {
+                Ok(cmd) => match cmd.run(self, &ctx, &msg).await {
+                    Ok(r) => r,
+                    Err(e) => format!("Failed to run command: {e}")
+                },
+                Err(usage) => usage
+            };
+            if let Err(e) = msg.reply_ping(ctx, reply).await {
+                eprintln!("{e}");
+            }
+            return;
         }
 
         // Otherwise go on
-        let create_info = match parse_text(&msg.content) {
-            Ok(r) => r,
+        let blocks = split_blocks(&msg.content);
+        if blocks.len() > 1 {
+            // every reminder would need its own copy of the files
+            let reply = if msg.attachments.is_empty() {
+                self.add_tasks_bulk(&ctx, &msg.author.id, &blocks).await
+            } else {
+                "files only go on a single reminder, send them along with the one they're for".into()
+            };
+            if let Err(e) = msg.reply_ping(ctx, reply).await {
+                eprintln!("{e}");
+            }
+            return;
+        }
+
+        let attachments = match attachments::download(&msg.attachments).await {
+            Ok(a) => a,
+            Err(e) => {
+                if let Err(e) = msg.reply_ping(ctx, e).await {
+                    eprintln!("{e}");
+                }
+                return;
+            }
+        };
+
+        let create_info = match self.parse_task(&msg.author.id, &msg.content).await {
+            Ok(r) => TaskCreateInfo { attachments, ..r },
             Err(err_string) => {
                 // might be:
                 // HELP, TASKS, DELETE
@@ -240,35 +1081,53 @@ impl EventHandler for DZBot {
             }
         };
 
-        if create_info.on_days.is_none() && create_info.date.is_none() {
-            msg.reply_ping(ctx, String::from("bro gave me no info")).await
-                .expect("couldnt alert user of failure");
-            return;
-        }
-
         // peak error management?
-        let task = match self.db.add_task(&msg.author.id, &create_info).await {
+        let task = match self.add_task(&ctx, &msg.author.id, &create_info).await {
             Ok(t) => t,
             Err(e) => {
-                msg.reply_ping(ctx, format!("Failed to save task to db: {e}")).await
-                    .expect("couldnt alert user of failure");
+                if let Err(e) = msg.reply_ping(ctx, format!("{e:#}")).await {
+                    eprintln!("Couldn't alert user of failure: {e}");
+                }
                 return;
             }
         };
-        match self.scheduler.add_task(ctx.http.clone(), &task).await {
-            Err(e) => {
-                msg.reply_ping(ctx, format!("Failed to schedule task: {e}")).await
-                    .expect("couldnt alert user of failure");
-                return;
-            }
-            Ok(_c) => {
-                println!("success");
-            }
-        }
 
         // Send back info to user
-        let reply = reply_string(&task);
-        msg.reply_ping(ctx, reply).await
-            .expect("couldnt alert user of SUCCESS??");
+        let mut reply = reply_string(&task);
+        if !create_info.attachments.is_empty() {
+            reply.push_str(&format!("\nwith **{}** attached files", create_info.attachments.len()));
+        }
+        if let Err(e) = msg.reply_ping(ctx, reply).await {
+            eprintln!("Couldn't alert user of success: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_blank_lines_after_a_time() {
+        let content = "water plants\n9am mon\n\nstretch\nafter 12 45m\n";
+        assert_eq!(split_blocks(content), vec!["water plants\n9am mon", "stretch\nafter 12 45m"]);
+    }
+
+    #[test]
+    fn keeps_blank_lines_inside_the_info() {
+        let content = "pack\nsocks\n\nshoes\n9am mon";
+        assert_eq!(split_blocks(content), vec!["pack\nsocks\n\nshoes\n9am mon"]);
+    }
+
+    #[test]
+    fn doesnt_choke_on_lines_that_almost_look_like_times() {
+        let content = "meds\n9am 13/40\n\nstretch\n9am mon";
+        assert!(split_blocks(content)[0].starts_with("meds\n9am 13/40"));
+    }
+
+    #[test]
+    fn always_splits_on_dashes() {
+        let content = "\n\none\n9am mon\n---\n---\ntwo\nnot a time\n---\n\n";
+        assert_eq!(split_blocks(content), vec!["one\n9am mon", "two\nnot a time"]);
     }
 }
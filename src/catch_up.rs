@@ -0,0 +1,140 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use serenity::all::{ButtonStyle, CreateActionRow, CreateButton, CreateMessage, Http, UserId};
+use ::time::OffsetDateTime;
+use tokio::time;
+
+use crate::{bot::DzContext, checklist, commands::join_capped, components::ComponentId, database::Database, jobs::due_times_between, model::Task, outbox::Lane};
+
+/// How often the bot notes that it's still running.
+const HEARTBEAT: Duration = Duration::from_secs(60);
+/// Reminders due longer ago than this aren't worth bringing up anymore.
+const MAX_LOOKBACK: ::time::Duration = ::time::Duration::days(30);
+/// Discord fits 5 rows of 5 buttons under a message.
+const MAX_BUTTONS: usize = 25;
+const MAX_LABEL_LEN: usize = 40;
+
+/// A task that was due at least once while the bot was down.
+struct Missed {
+    task: Task,
+    /// The latest missed occurrence, the one sent again on request
+    occurrence_id: i64,
+    due_at: OffsetDateTime,
+    times: usize,
+}
+
+/// What was missed between the last heartbeat before a restart and the restart.
+pub struct MissedWhileOffline {
+    since: OffsetDateTime,
+    until: OffsetDateTime,
+    by_user: HashMap<UserId, Vec<Missed>>,
+}
+
+/// Keeps telling the database the bot is up, until the task is dropped.
+pub async fn heartbeat(db: Arc<Database>) {
+    let mut interval = time::interval(HEARTBEAT);
+    loop {
+        interval.tick().await;
+        if let Err(e) = db.beat().await {
+            eprintln!("Failed to record heartbeat: {e}");
+        }
+    }
+}
+
+/// Records every time a task was due since the last heartbeat as a missed occurrence.
+/// Has to run before the tasks are rescheduled, which archives the ones that are past by now.
+/// None on the first run, when there's no heartbeat to go by.
+pub async fn record_missed(db: &Database, tasks: &[Task], now: OffsetDateTime) -> Result<Option<MissedWhileOffline>> {
+    let Some(last_seen) = db.last_seen().await? else {
+        return Ok(None);
+    };
+    let since = last_seen.max(now - MAX_LOOKBACK);
+
+    let mut by_user: HashMap<UserId, Vec<Missed>> = HashMap::new();
+    for task in tasks.iter().filter(|t| !t.paused()) {
+        let checklist_len = checklist::items(&task.remind_info().info).len() as i32;
+        let mut latest = None;
+        let mut times = 0;
+        for due_at in due_times_between(task, since, now) {
+            // None when it went out just before the bot went down
+            if let Some(occurrence) = db.add_missed_occurrence(task.id(), task.user_id(), due_at, checklist_len).await? {
+                latest = Some(occurrence);
+                times += 1;
+            }
+        }
+
+        if let Some(occurrence) = latest {
+            by_user.entry(*task.user_id()).or_default().push(Missed {
+                task: task.clone(),
+                occurrence_id: occurrence.id,
+                due_at: occurrence.due_at,
+                times
+            });
+        }
+    }
+
+    println!("found missed reminders for {} users since {since}", by_user.len());
+    Ok(Some(MissedWhileOffline { since, until: now, by_user }))
+}
+
+impl MissedWhileOffline {
+    /// One message per user, with a button to send each reminder now.
+    pub async fn send_summaries(self, ctx: &DzContext, http: Arc<Http>) {
+        for (user_id, mut missed) in self.by_user {
+            missed.sort_by_key(|m| m.due_at);
+            let message = summary(self.since, self.until, &missed);
+            if let Err(e) = send_summary(ctx, http.clone(), user_id, message).await {
+                eprintln!("Failed to send missed reminders to {user_id}: {e}");
+            }
+        }
+    }
+}
+
+async fn send_summary(ctx: &DzContext, http: Arc<Http>, user_id: UserId, message: CreateMessage) -> Result<()> {
+    let channel = ctx.read().await.get_dm_channel(http, user_id).await?;
+    let outbox = ctx.read().await.outbox.clone();
+    outbox.send(channel, message, Lane::First).await?;
+    Ok(())
+}
+
+fn summary(since: OffsetDateTime, until: OffsetDateTime, missed: &[Missed]) -> CreateMessage {
+    let lines = missed.iter()
+        .map(|m| {
+            let mut line = format!(
+                "`{}` **{}** due <t:{}:f>",
+                m.task.id(),
+                m.task.remind_info().title,
+                m.due_at.unix_timestamp()
+            );
+            if m.times > 1 {
+                line.push_str(&format!(" (×{})", m.times));
+            }
+            line
+        })
+        .collect();
+    let content = format!(
+        "I was offline from <t:{}:f> to <t:{}:f> and missed these:\n{}\n\ntap one to get it now",
+        since.unix_timestamp(),
+        until.unix_timestamp(),
+        join_capped(lines, "\n")
+    );
+
+    // the latest ones if they don't all fit
+    let buttons = missed.iter()
+        .skip(missed.len().saturating_sub(MAX_BUTTONS))
+        .map(|m| {
+            let label: String = m.task.remind_info().title.chars().take(MAX_LABEL_LEN).collect();
+            CreateButton::new(ComponentId::Redeliver { occurrence_id: m.occurrence_id }.encode())
+                .label(label)
+                .style(ButtonStyle::Primary)
+        })
+        .collect::<Vec<_>>();
+    let rows = buttons.chunks(5)
+        .map(|row| CreateActionRow::Buttons(row.to_vec()))
+        .collect();
+
+    CreateMessage::new()
+        .content(content)
+        .components(rows)
+}
@@ -0,0 +1,84 @@
+use rand::{seq::SliceRandom, Rng};
+
+/// Left out 0/O and 1/I, they're easy to mix up half-asleep.
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 6;
+/// Goes between the characters of a code so it can't just be copied and pasted back.
+const ZERO_WIDTH_SPACE: char = '\u{200b}';
+
+/// What has to be answered to stop a task's nag, instead of the ack phrase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeKind {
+    /// A bit of arithmetic
+    Math,
+    /// Retyping a random code
+    Code
+}
+
+impl ChallengeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Math => "math",
+            Self::Code => "code"
+        }
+    }
+
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        Ok(
+            match s {
+                "math" => Self::Math,
+                "code" => Self::Code,
+                _ => anyhow::bail!("Invalid challenge: {s}")
+            }
+        )
+    }
+
+    /// A new question, every nag gets its own.
+    pub fn generate(&self) -> Challenge {
+        let mut rng = rand::thread_rng();
+        match self {
+            Self::Math => {
+                let (a, b, c) = (rng.gen_range(3..=12), rng.gen_range(3..=12), rng.gen_range(10..=99));
+                Challenge {
+                    kind: *self,
+                    question: format!("{a} × {b} + {c}"),
+                    answer: (a * b + c).to_string()
+                }
+            },
+            Self::Code => {
+                let code: String = (0..CODE_LEN)
+                    .map(|_| *CODE_CHARS.choose(&mut rng).unwrap() as char)
+                    .collect();
+                Challenge {
+                    kind: *self,
+                    question: code.chars().map(String::from).collect::<Vec<_>>().join(&ZERO_WIDTH_SPACE.to_string()),
+                    answer: code
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    kind: ChallengeKind,
+    /// Shown to the user
+    pub question: String,
+    answer: String
+}
+
+impl Challenge {
+    /// Case and spaces don't matter, a pasted code does.
+    pub fn check(&self, message: &str) -> bool {
+        let message: String = message.chars().filter(|c| !c.is_whitespace()).collect();
+        message.eq_ignore_ascii_case(&self.answer)
+    }
+
+    /// What to tell the user so they know how to stop the nag.
+    pub fn prompt(&self) -> String {
+        match self.kind {
+            ChallengeKind::Math => format!("solve `{}` to stop the pings", self.question),
+            ChallengeKind::Code => format!("type `{}` to stop the pings", self.question)
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use serenity::all::{ButtonStyle, CreateActionRow, CreateButton};
+
+use crate::components::ComponentId;
+
+/// Discord allows at most 25 buttons on a message and the last row is for done/snooze/skip,
+/// any items after that are plain text.
+const MAX_ITEMS: usize = 20;
+const BUTTONS_PER_ROW: usize = 5;
+const MAX_LABEL_LEN: usize = 80;
+
+/// Returns the text of the line if it looks like `- [ ] item` or `- [x] item`.
+fn item_text(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    ["- [ ]", "- [x]", "- [X]"].iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+/// The checklist items in the task info, in order.
+pub fn items(info: &str) -> Vec<&str> {
+    info.lines()
+        .filter_map(item_text)
+        .take(MAX_ITEMS)
+        .collect()
+}
+
+/// Renders the task info with the checklist lines replaced by their current state.
+pub fn render(info: &str, checked: &[i32]) -> String {
+    let mut idx = 0;
+    info.lines()
+        .map(|line| {
+            let Some(text) = item_text(line).filter(|_| idx < MAX_ITEMS) else {
+                return line.to_string();
+            };
+            let ticked = checked.contains(&(idx as i32));
+            idx += 1;
+            if ticked {
+                format!("✅ ~~{text}~~")
+            } else {
+                format!("⬜ {text}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One button per item. Disabled once the occurrence is no longer pending.
+pub fn buttons(occurrence_id: i64, info: &str, checked: &[i32], disabled: bool) -> Vec<CreateActionRow> {
+    items(info)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, text)| {
+            let item = idx as i32;
+            let id = ComponentId::CheckItem { occurrence_id, item };
+            let label: String = text.chars().take(MAX_LABEL_LEN).collect();
+            CreateButton::new(id.encode())
+                .label(label)
+                .style(if checked.contains(&item) { ButtonStyle::Success } else { ButtonStyle::Secondary })
+                .disabled(disabled)
+        })
+        .collect::<Vec<_>>()
+        .chunks(BUTTONS_PER_ROW)
+        .map(|row| CreateActionRow::Buttons(row.to_vec()))
+        .collect()
+}
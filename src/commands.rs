@@ -0,0 +1,793 @@
+use anyhow::Result;
+use itertools::Itertools;
+use serenity::all::{ButtonStyle, Context, CreateActionRow, CreateButton, CreateMessage, Mentionable, Message, UserId};
+use time::{Duration, OffsetDateTime};
+
+use crate::{ack, attachments, bot::{reply_string, DZBot}, components::ComponentId, database::Database, jobs::{next_occurrence_time, waits_for}, model::{Action, ArchiveReason, PingMode, Task, Template}, outbox::Lane, stats::HabitStats, time_parse::{format_duration, parse_duration, parse_duration_list}};
+
+/// Discord rejects messages over 2000 characters.
+const MAX_REPLY_LEN: usize = 1900;
+
+/// Commands that can be sent instead of a task.
+/// All of them are a single line, except `template save` and `edit` which take a task after them.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Stats,
+    Tasks(TaskFilter),
+    Pause(TaskFilter),
+    Resume(TaskFilter),
+    Delete(TaskFilter),
+    /// Shows the offsets if None
+    DeadlineOffsets(Option<Vec<Duration>>),
+    /// `body` is the task to save, same format as a normal task
+    TemplateSave { name: String, body: String },
+    TemplateList,
+    TemplateDelete(String),
+    /// Creates a task from a template, optionally with a different time line
+    FromTemplate { name: String, time_line: Option<String> },
+    /// `body` replaces the task, same format as a normal task
+    Edit { id: i64, body: String },
+    /// Reverses the latest create, delete, edit, pause or resume
+    Undo,
+    Archive,
+    Restore(i64),
+    Nag(NagChange),
+    /// Shows the ack phrase if None
+    AckPhrase(Option<String>),
+    Buddy(BuddyChange),
+}
+
+/// What `buddy` does.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BuddyChange {
+    Show,
+    /// Sends the buddy a consent prompt, for one task or every task if None
+    Ask { buddy: UserId, task_id: Option<i64>, after: Duration },
+    Remove(Option<i64>),
+}
+
+/// What `nag` changes about the user's nag cadence.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NagChange {
+    Show,
+    StartInterval(Duration),
+    Interval(Duration),
+    GiveUpAfter(Duration),
+    Lines(Vec<String>),
+    Mode(PingMode),
+}
+
+/// Which tasks a command applies to, e.g. `12 15 #work`.
+/// A task matches if it has any of the ids or any of the tags.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TaskFilter {
+    pub ids: Vec<i64>,
+    pub tags: Vec<String>,
+}
+
+impl TaskFilter {
+    fn parse<'a>(args: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for arg in args {
+            if let Some(tag) = arg.strip_prefix('#') {
+                filter.tags.push(tag.to_lowercase());
+            } else {
+                let id = arg.parse()
+                    .map_err(|_| format!("`{arg}` is not a task id or #tag"))?;
+                filter.ids.push(id);
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Returns the filter or an error if it's empty, so a bare `delete` can't hit every task.
+    fn required(self, cmd: &str) -> Result<Self, String> {
+        if self.is_empty() {
+            return Err(format!("{cmd} what? give me task ids or #tags, like `{cmd} 12 #work`"));
+        }
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty() && self.tags.is_empty()
+    }
+
+    /// An empty filter matches everything.
+    pub fn matches(&self, task: &Task) -> bool {
+        self.is_empty()
+        || self.ids.contains(&task.id())
+        || task.tags().iter().any(|t| self.tags.contains(t))
+    }
+}
+
+impl Command {
+    /// None if the message isn't a command,
+    /// otherwise the command or why its arguments are wrong.
+    pub fn parse(content: &str) -> Option<Result<Self, String>> {
+        let content = content.trim();
+        let (content, body) = match content.split_once('\n') {
+            Some((first, body)) => (first.trim(), Some(body.trim())),
+            None => (content, None)
+        };
+
+        let mut words = content.split_whitespace();
+        let cmd = words.next()?.to_lowercase();
+
+        if let Some(body) = body {
+            // anything else with more lines is a task
+            return match cmd.as_str() {
+                "template" if words.next().is_some_and(|w| w.eq_ignore_ascii_case("save")) => 
+                    Some(parse_template_save(words, body)),
+                "edit" => Some(parse_edit(words, body)),
+                _ => None
+            };
+        }
+
+        let filter = TaskFilter::parse(words.clone());
+        Some(
+            match cmd.as_str() {
+                "stats" => Ok(Self::Stats),
+                "tasks" => filter.map(Self::Tasks),
+                "pause" => filter.and_then(|f| f.required("pause")).map(Self::Pause),
+                "resume" => filter.and_then(|f| f.required("resume")).map(Self::Resume),
+                "delete" => filter.and_then(|f| f.required("delete")).map(Self::Delete),
+                "deadline" => parse_deadline(content),
+                "template" | "templates" => parse_template(words),
+                "t" => parse_from_template(words),
+                "edit" => Err("put the new reminder on the next lines, like\n`edit 12`\n`title`\n`5pm MWF`".into()),
+                "undo" => Ok(Self::Undo),
+                "archive" => parse_archive(words),
+                "nag" => parse_nag(words),
+                "phrase" => parse_ack_phrase(words),
+                "buddy" => parse_buddy(words),
+                _ => return None
+            }
+        )
+    }
+
+    /// Returns the reply to send back to the user.
+    pub async fn run(self, bot: &DZBot, ctx: &Context, msg: &Message) -> Result<String> {
+        let user_id = &msg.author.id;
+        match self {
+            Self::Stats => stats(&bot.db, user_id).await,
+            Self::Tasks(filter) => tasks(&bot.db, user_id, &filter).await,
+            Self::Pause(filter) => pause(bot, user_id, &filter).await,
+            Self::Resume(filter) => resume(bot, ctx, user_id, &filter).await,
+            Self::Delete(filter) => delete(bot, user_id, &filter).await,
+            Self::DeadlineOffsets(offsets) => deadline_offsets(&bot.db, user_id, offsets).await,
+            Self::TemplateSave { name, body } => template_save(bot, user_id, name, &body).await,
+            Self::TemplateList => template_list(&bot.db, user_id).await,
+            Self::TemplateDelete(name) => template_delete(&bot.db, user_id, &name).await,
+            Self::FromTemplate { name, time_line } => 
+                from_template(bot, ctx, user_id, &name, time_line.as_deref()).await,
+            Self::Edit { id, body } => edit(bot, ctx, msg, id, &body).await,
+            Self::Undo => undo(bot, ctx, user_id).await,
+            Self::Archive => archive(&bot.db, user_id).await,
+            Self::Restore(id) => restore(bot, ctx, user_id, id).await,
+            Self::Nag(change) => nag(bot, user_id, change).await,
+            Self::AckPhrase(phrase) => ack_phrase(&bot.db, user_id, phrase).await,
+            Self::Buddy(change) => buddy(bot, ctx, user_id, change).await,
+        }
+    }
+}
+
+/// `deadline offsets [7d,1d,3h]`
+fn parse_deadline(content: &str) -> Result<Command, String> {
+    let mut words = content.split_whitespace().skip(1);
+    if !words.next().is_some_and(|w| w.eq_ignore_ascii_case("offsets")) {
+        return Err(
+            "deadlines go on the last line of a reminder, like `deadline 11/30 5pm`\n\
+            or change when they remind you with `deadline offsets 7d,1d,3h`".into()
+        );
+    }
+    let list = words.collect::<String>();
+    if list.is_empty() {
+        return Ok(Command::DeadlineOffsets(None));
+    }
+    parse_duration_list(&list).map(|o| Command::DeadlineOffsets(Some(o)))
+}
+
+const TEMPLATE_USAGE: &str = "\
+template save <name> - with a reminder on the next lines
+template list
+template delete <name>
+t <name> [time] - create a reminder from a template, optionally at a different time";
+
+/// `template save <name>` followed by a task
+fn parse_template_save<'a>(mut words: impl Iterator<Item = &'a str>, body: &str) -> Result<Command, String> {
+    let name = words.next().ok_or(String::from(TEMPLATE_USAGE))?.to_lowercase();
+    Ok(Command::TemplateSave { name, body: body.into() })
+}
+
+/// `template list` or `template delete <name>`
+fn parse_template<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Command, String> {
+    match words.next().map(str::to_lowercase).as_deref() {
+        None | Some("list") => Ok(Command::TemplateList),
+        Some("delete") => words.next()
+            .map(|n| Command::TemplateDelete(n.to_lowercase()))
+            .ok_or(TEMPLATE_USAGE.into()),
+        Some("save") => Err("put the reminder to save on the next lines".into()),
+        _ => Err(TEMPLATE_USAGE.into())
+    }
+}
+
+/// `t <name> [time]`
+fn parse_from_template<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Command, String> {
+    let name = words.next().ok_or(String::from(TEMPLATE_USAGE))?.to_lowercase();
+    let time_line = words.join(" ");
+    Ok(Command::FromTemplate { 
+        name, 
+        time_line: (!time_line.is_empty()).then_some(time_line) 
+    })
+}
+
+/// `edit <id>` followed by a task
+fn parse_edit<'a>(mut words: impl Iterator<Item = &'a str>, body: &str) -> Result<Command, String> {
+    let id = words.next()
+        .and_then(|w| w.parse().ok())
+        .ok_or(String::from("edit which task? like `edit 12`"))?;
+    Ok(Command::Edit { id, body: body.into() })
+}
+
+/// `archive` or `archive restore <id>`
+fn parse_archive<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Command, String> {
+    match words.next().map(str::to_lowercase).as_deref() {
+        None => Ok(Command::Archive),
+        Some("restore") => words.next()
+            .and_then(|w| w.parse().ok())
+            .map(Command::Restore)
+            .ok_or("restore which task? like `archive restore 12`".into()),
+        _ => Err("`archive` to list old reminders, `archive restore <id>` to bring one back".into())
+    }
+}
+
+/// Pinging faster than this runs into discord's rate limits.
+const MIN_NAG_INTERVAL: Duration = Duration::seconds(1);
+const MIN_NAG_GIVE_UP: Duration = Duration::minutes(1);
+const MAX_NAG_TEXT_LEN: usize = 200;
+const MAX_NAG_LINES: usize = 10;
+
+const NAG_USAGE: &str = "\
+nag - show how you get nagged
+nag start <duration> - time between the first pings, like `nag start 1m`
+nag every <duration> - time between pings once it's sped up, like `nag every 5s`
+nag limit <duration> - give up and count it as missed after this long, like `nag limit 30m`
+nag text <line> | <line> ... - what the pings say, taking turns. {title} is the reminder's title
+nag mode ghost/persistent/counter - delete pings right away, leave them, or keep one message with a count";
+
+/// `nag [start <duration> | every <duration> | limit <duration> | text <lines> | mode <mode>]`
+fn parse_nag<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Command, String> {
+    let change = match words.next().map(str::to_lowercase).as_deref() {
+        None => NagChange::Show,
+        Some("start") => NagChange::StartInterval(nag_duration(words.next(), MIN_NAG_INTERVAL)?),
+        Some("every") => NagChange::Interval(nag_duration(words.next(), MIN_NAG_INTERVAL)?),
+        Some("limit") => NagChange::GiveUpAfter(nag_duration(words.next(), MIN_NAG_GIVE_UP)?),
+        Some("text") => {
+            let text = words.join(" ");
+            let lines = text.split('|')
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect_vec();
+            if lines.is_empty() {
+                return Err(NAG_USAGE.into());
+            }
+            if lines.len() > MAX_NAG_LINES {
+                return Err(format!("there can be at most {MAX_NAG_LINES} nag lines"));
+            }
+            if lines.iter().any(|l| l.chars().count() > MAX_NAG_TEXT_LEN) {
+                return Err(format!("nag lines can be at most {MAX_NAG_TEXT_LEN} characters"));
+            }
+            NagChange::Lines(lines)
+        },
+        Some("mode") => words.next()
+            .and_then(|m| PingMode::parse(&m.to_lowercase()).ok())
+            .map(NagChange::Mode)
+            .ok_or(String::from(NAG_USAGE))?,
+        _ => return Err(NAG_USAGE.into())
+    };
+    Ok(Command::Nag(change))
+}
+
+/// `phrase [new phrase]`
+fn parse_ack_phrase<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Command, String> {
+    let text = words.join(" ");
+    if text.is_empty() {
+        return Ok(Command::AckPhrase(None));
+    }
+    ack::parse_phrase(&text).map(|p| Command::AckPhrase(Some(p)))
+}
+
+const DEFAULT_BUDDY_AFTER: Duration = Duration::minutes(30);
+const MIN_BUDDY_AFTER: Duration = Duration::minutes(1);
+/// How long someone who hasn't said no yet gets left alone after a prompt.
+const BUDDY_ASK_COOLDOWN: Duration = Duration::days(1);
+
+const BUDDY_USAGE: &str = "\
+buddy - show your buddies
+buddy @someone [id] [after] - ask someone to get a DM when you leave reminder `id` (or any reminder) unchecked, after 30m by default
+buddy remove [id] - remove the buddy for reminder `id`, or the one for every reminder";
+
+/// `buddy [@someone [id] [after] | remove [id]]`
+fn parse_buddy<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Command, String> {
+    let change = match words.next() {
+        None => BuddyChange::Show,
+        Some(w) if w.eq_ignore_ascii_case("remove") => match words.next() {
+            None => BuddyChange::Remove(None),
+            Some(id) => BuddyChange::Remove(Some(id.parse().map_err(|_| String::from(BUDDY_USAGE))?))
+        },
+        Some(mention) => {
+            let buddy = mention.trim_start_matches("<@").trim_start_matches('!').trim_end_matches('>')
+                .parse::<u64>().ok()
+                .filter(|&id| id != 0)
+                .map(UserId::new)
+                .ok_or(format!("`{mention}` isn't someone, mention them like `buddy @someone`"))?;
+            let (mut task_id, mut after) = (None, DEFAULT_BUDDY_AFTER);
+            for word in words {
+                if let Ok(id) = word.parse() {
+                    task_id = Some(id);
+                } else if let Some(dur) = parse_duration(word) {
+                    if dur < MIN_BUDDY_AFTER {
+                        return Err(format!("that's too short, it has to be at least {}", format_duration(MIN_BUDDY_AFTER)));
+                    }
+                    after = dur;
+                } else {
+                    return Err(BUDDY_USAGE.into());
+                }
+            }
+            BuddyChange::Ask { buddy, task_id, after }
+        }
+    };
+    Ok(Command::Buddy(change))
+}
+
+fn nag_duration(word: Option<&str>, min: Duration) -> Result<Duration, String> {
+    let dur = word
+        .and_then(parse_duration)
+        .ok_or(String::from(NAG_USAGE))?;
+    if dur < min {
+        return Err(format!("that's too short, it has to be at least {}", format_duration(min)));
+    }
+    Ok(dur)
+}
+
+async fn matching_tasks(db: &Database, user_id: &UserId, filter: &TaskFilter) -> Result<Vec<Task>> {
+    Ok(
+        db.tasks_for(user_id).await?
+            .into_iter()
+            .filter(|t| filter.matches(t))
+            .collect()
+    )
+}
+
+/// Joins the lines, cutting off the ones that don't fit in a message.
+pub fn join_capped(lines: Vec<String>, sep: &str) -> String {
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if out.len() + line.len() + sep.len() > MAX_REPLY_LEN {
+            out.push_str(&format!("{sep}...and {} more", lines.len() - i));
+            break;
+        }
+        if i > 0 {
+            out.push_str(sep);
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+pub fn task_line(task: &Task) -> String {
+    let mut line = format!("`{}` **{}**", task.id(), task.remind_info().title);
+    if task.paused() {
+        line.push_str(" (paused)");
+    } else if let Some(next) = next_occurrence_time(task) {
+        line.push_str(&format!(" next <t:{}:f>", next.unix_timestamp()));
+    } else if let Some((after, delay)) = task.chained_after() {
+        line.push_str(&format!(" {} after `{after}` is done", format_duration(delay)));
+    } else {
+        line.push_str(" (never runs)");
+    }
+    if !task.tags().is_empty() {
+        line.push_str(&format!(" {}", task.tags().iter().map(|t| format!("#{t}")).join(" ")));
+    }
+    line
+}
+
+async fn stats(db: &Database, user_id: &UserId) -> Result<String> {
+    let now = OffsetDateTime::now_utc();
+    let mut lines = vec![];
+
+    for task in db.tasks_for(user_id).await? {
+        if !task.recurring() {
+            continue;
+        }
+        let history = db.occurrences_for(task.id()).await?;
+        let stats = HabitStats::compute(&history, now);
+        lines.push(format!("**{}** (`{}`)\n{}", task.remind_info().title, task.id(), stats.footer()));
+    }
+
+    if lines.is_empty() {
+        return Ok("no recurring reminders yet".into());
+    }
+
+    Ok(join_capped(lines, "\n\n"))
+}
+
+async fn tasks(db: &Database, user_id: &UserId, filter: &TaskFilter) -> Result<String> {
+    let lines = matching_tasks(db, user_id, filter).await?
+        .iter()
+        .map(task_line)
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        return Ok("no reminders found".into());
+    }
+
+    Ok(join_capped(lines, "\n"))
+}
+
+async fn pause(bot: &DZBot, user_id: &UserId, filter: &TaskFilter) -> Result<String> {
+    let to_pause = matching_tasks(&bot.db, user_id, filter).await?
+        .into_iter()
+        .filter(|t| !t.paused())
+        .collect::<Vec<_>>();
+    bot.log_action(user_id, Action::Pause, &to_pause.iter().map(Task::id).collect::<Vec<_>>()).await;
+
+    let mut paused = vec![];
+    for task in to_pause {
+        bot.db.set_paused(task.id(), true).await?;
+        bot.ctx.read().await.pause_reminder_job(task.id());
+        paused.push(task_line(&task));
+    }
+
+    if paused.is_empty() {
+        return Ok("nothing to pause".into());
+    }
+
+    Ok(format!("paused:\n{}", join_capped(paused, "\n")))
+}
+
+async fn resume(bot: &DZBot, ctx: &Context, user_id: &UserId, filter: &TaskFilter) -> Result<String> {
+    let to_resume = matching_tasks(&bot.db, user_id, filter).await?
+        .into_iter()
+        .filter(|t| t.paused())
+        .collect::<Vec<_>>();
+    bot.log_action(user_id, Action::Resume, &to_resume.iter().map(Task::id).collect::<Vec<_>>()).await;
+
+    let mut resumed = vec![];
+    for task in to_resume {
+        bot.db.set_paused(task.id(), false).await?;
+        let task = bot.db.task(task.id()).await?;
+        bot.scheduler.add_task(ctx.http.clone(), &task).await?;
+        resumed.push(task_line(&task));
+    }
+
+    if resumed.is_empty() {
+        return Ok("nothing to resume".into());
+    }
+
+    Ok(format!("resumed:\n{}", join_capped(resumed, "\n")))
+}
+
+async fn delete(bot: &DZBot, user_id: &UserId, filter: &TaskFilter) -> Result<String> {
+    let to_delete = matching_tasks(&bot.db, user_id, filter).await?;
+    bot.log_action(user_id, Action::Delete, &to_delete.iter().map(Task::id).collect::<Vec<_>>()).await;
+
+    let mut deleted = vec![];
+    for task in to_delete {
+        // paused tasks have no job, so archive the row here as well
+        bot.ctx.read().await.kill_reminder_job(task.id());
+        bot.ctx.write().await.stop_nags_of_task(task.id()).await;
+        bot.db.archive_task(task.id(), ArchiveReason::Cancelled).await?;
+        deleted.push(format!("`{}` **{}**", task.id(), task.remind_info().title));
+    }
+
+    if deleted.is_empty() {
+        return Ok("nothing to delete".into());
+    }
+
+    Ok(format!("deleted, `undo` or `archive restore <id>` brings them back:\n{}", join_capped(deleted, "\n")))
+}
+
+async fn deadline_offsets(db: &Database, user_id: &UserId, offsets: Option<Vec<Duration>>) -> Result<String> {
+    let mut settings = db.settings(user_id).await?;
+    if let Some(offsets) = offsets {
+        settings.deadline_offsets = offsets.into_iter().sorted().rev().dedup().collect();
+        db.put_settings(user_id, settings.clone()).await?;
+    }
+
+    Ok(format!(
+        "new deadlines remind you **{}** before",
+        settings.deadline_offsets.into_iter().map(format_duration).join(", ")
+    ))
+}
+
+async fn template_save(bot: &DZBot, user_id: &UserId, name: String, body: &str) -> Result<String> {
+    // make sure it would actually create a task
+    if let Err(e) = bot.parse_task(user_id, body).await {
+        return Ok(format!("that reminder doesn't work: {e}"));
+    }
+
+    let mut lines = body.lines();
+    let title = lines.next().unwrap_or_default().to_string();
+    let time_line = lines.next_back().unwrap_or_default().to_string();
+    let info = lines.join("\n");
+    let template = Template { name, title, info, time_line };
+    bot.db.put_template(user_id, &template).await?;
+
+    Ok(format!("saved template **{}**, use it with `t {}`", template.name, template.name))
+}
+
+async fn template_list(db: &Database, user_id: &UserId) -> Result<String> {
+    let lines = db.templates_for(user_id).await?
+        .into_iter()
+        .map(|t| format!("**{}**: {} `{}`", t.name, t.title, t.time_line))
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        return Ok(format!("no templates yet\n{TEMPLATE_USAGE}"));
+    }
+
+    Ok(join_capped(lines, "\n"))
+}
+
+async fn template_delete(db: &Database, user_id: &UserId, name: &str) -> Result<String> {
+    Ok(
+        if db.delete_template(user_id, name).await? {
+            format!("deleted template **{name}**")
+        } else {
+            format!("no template called **{name}**")
+        }
+    )
+}
+
+async fn from_template(
+    bot: &DZBot, 
+    ctx: &Context, 
+    user_id: &UserId, 
+    name: &str, 
+    time_line: Option<&str>
+) -> Result<String> {
+    let Some(template) = bot.db.template(user_id, name).await? else {
+        return Ok(format!("no template called **{name}**"));
+    };
+
+    let create_info = match bot.parse_task(user_id, &template.to_text(time_line)).await {
+        Ok(c) => c,
+        Err(e) => return Ok(e)
+    };
+    let task = bot.add_task(ctx, user_id, &create_info).await?;
+
+    Ok(reply_string(&task))
+}
+
+/// New attachments replace the old ones, without any the old ones are kept.
+async fn edit(bot: &DZBot, ctx: &Context, msg: &Message, id: i64, body: &str) -> Result<String> {
+    let user_id = &msg.author.id;
+    if !bot.db.tasks_for(user_id).await?.iter().any(|t| t.id() == id) {
+        return Ok(format!("you don't have a reminder `{id}`"));
+    }
+
+    let mut create_info = match bot.parse_task(user_id, body).await {
+        Ok(c) => c,
+        Err(e) => return Ok(e)
+    };
+    create_info.attachments = match attachments::download(&msg.attachments).await {
+        Ok(a) => a,
+        Err(e) => return Ok(e)
+    };
+    bot.log_action(user_id, Action::Edit, &[id]).await;
+    let task = bot.db.update_task(id, &create_info).await?;
+    bot.reschedule(ctx, &task).await?;
+
+    Ok(format!("edited `{id}`\n{}", reply_string(&task)))
+}
+
+async fn undo(bot: &DZBot, ctx: &Context, user_id: &UserId) -> Result<String> {
+    let Some(undone) = bot.db.undo(user_id).await? else {
+        return Ok("nothing to undo".into());
+    };
+
+    for task in &undone.tasks {
+        if undone.action == Action::Create {
+            // already archived, just stop it
+            bot.ctx.read().await.pause_reminder_job(task.id());
+        } else {
+            bot.reschedule(ctx, task).await?;
+        }
+    }
+
+    let (what, lines) = match undone.action {
+        Action::Create => (
+            "archived what you created, `archive restore <id>` brings one back",
+            undone.tasks.iter()
+                .map(|t| format!("`{}` **{}**", t.id(), t.remind_info().title))
+                .collect::<Vec<_>>()
+        ),
+        action => (
+            match action {
+                Action::Delete => "restored",
+                Action::Edit => "changed back",
+                Action::Pause => "unpaused",
+                _ => "paused again",
+            },
+            undone.tasks.iter().map(task_line).collect()
+        )
+    };
+
+    if lines.is_empty() {
+        return Ok(format!("undid your last {}, but those reminders are already gone", undone.action.as_str()));
+    }
+
+    Ok(format!("{what}:\n{}", join_capped(lines, "\n")))
+}
+
+async fn archive(db: &Database, user_id: &UserId) -> Result<String> {
+    let lines = db.archived_tasks_for(user_id).await?
+        .into_iter()
+        .map(|a| format!(
+            "`{}` **{}** {} <t:{}:d>", 
+            a.task.id(), 
+            a.task.remind_info().title, 
+            a.reason.as_str(), 
+            a.archived_at.unix_timestamp()
+        ))
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        return Ok("the archive is empty".into());
+    }
+
+    Ok(join_capped(lines, "\n"))
+}
+
+async fn restore(bot: &DZBot, ctx: &Context, user_id: &UserId, id: i64) -> Result<String> {
+    let Some(task) = bot.db.restore_task(user_id, id).await? else {
+        return Ok(format!("there's no archived reminder `{id}`"));
+    };
+    // without any times left the job would archive it again straight away,
+    // unless it's chained and still waiting on the task before it
+    let waiting = match task.chained_after() {
+        Some((after, _)) => waits_for(&bot.ctx, after).await,
+        None => false
+    };
+    if next_occurrence_time(&task).is_none() && !waiting {
+        return Ok(format!("restored {}\nit has no times left, give it new ones with `edit {id}`", task_line(&task)));
+    }
+    bot.scheduler.add_task(ctx.http.clone(), &task).await?;
+
+    Ok(format!("restored {}", task_line(&task)))
+}
+
+async fn nag(bot: &DZBot, user_id: &UserId, change: NagChange) -> Result<String> {
+    let mut settings = bot.db.settings(user_id).await?;
+    let changed = change != NagChange::Show;
+    match change {
+        NagChange::Show => {},
+        NagChange::StartInterval(interval) => settings.nag.start_interval = interval,
+        NagChange::Interval(interval) => settings.nag.interval = interval,
+        NagChange::GiveUpAfter(after) => settings.nag.give_up_after = after,
+        NagChange::Lines(lines) => settings.nag.lines = lines,
+        NagChange::Mode(mode) => settings.nag.mode = mode,
+    }
+
+    if changed {
+        bot.db.put_settings(user_id, settings.clone()).await?;
+        // running nags pick it up from the next ping
+        for nag in bot.ctx.read().await.nags_ctl.values().filter(|n| n.user_id == *user_id) {
+            nag.set_cadence(settings.nag.clone());
+        }
+    }
+
+    let nag = settings.nag;
+    // the default is 1.5s, which format_duration would round down
+    let interval = |dur: Duration| if dur < Duration::minutes(1) {
+        format!("{}s", dur.as_seconds_f32())
+    } else {
+        format_duration(dur)
+    };
+    Ok(format!(
+        "pinging every **{}**, speeding up to every **{}**, giving up after **{}**\n\
+        pings are {} and take turns saying:\n{}",
+        interval(nag.start_interval),
+        interval(nag.interval),
+        format_duration(nag.give_up_after),
+        match nag.mode {
+            PingMode::Ghost => "deleted right away",
+            PingMode::Persistent => "left in the chat",
+            PingMode::Counter => "one message with a count"
+        },
+        nag.lines.iter().map(|l| format!("- {l}")).join("\n")
+    ))
+}
+
+async fn ack_phrase(db: &Database, user_id: &UserId, phrase: Option<String>) -> Result<String> {
+    let mut settings = db.settings(user_id).await?;
+    if let Some(phrase) = phrase {
+        settings.ack_phrase = phrase;
+        db.put_settings(user_id, settings.clone()).await?;
+    }
+
+    Ok(format!("say `{}` to stop the pings, small typos are fine", settings.ack_phrase))
+}
+
+async fn buddy(bot: &DZBot, ctx: &Context, user_id: &UserId, change: BuddyChange) -> Result<String> {
+    match change {
+        BuddyChange::Show => {
+            let buddies = bot.db.buddies_for(user_id).await?;
+            if buddies.is_empty() {
+                return Ok(format!("no buddies yet\n{BUDDY_USAGE}"));
+            }
+            let lines = buddies.iter()
+                .map(|b| format!(
+                    "{}: {} after **{}** ({})", 
+                    b.scope(), 
+                    b.buddy_id.mention(), 
+                    format_duration(b.after), 
+                    b.status.as_str()
+                ))
+                .collect();
+            Ok(join_capped(lines, "\n"))
+        },
+        BuddyChange::Remove(task_id) => Ok(
+            if bot.db.remove_buddy(user_id, task_id).await? {
+                "removed the buddy".into()
+            } else {
+                "there's no buddy for that".into()
+            }
+        ),
+        BuddyChange::Ask { buddy, task_id, after } => {
+            if buddy == *user_id {
+                return Ok("you can't be your own buddy".into());
+            }
+            if let Some(id) = task_id {
+                if !bot.db.tasks_for(user_id).await?.iter().any(|t| t.id() == id) {
+                    return Ok(format!("you don't have a reminder `{id}`"));
+                }
+            }
+            match buddy.to_user(ctx).await {
+                Ok(u) if u.bot => return Ok("bots can't be buddies".into()),
+                Ok(_) => {},
+                Err(_) => return Ok(format!("couldn't find {}", buddy.mention()))
+            }
+            match bot.db.last_buddy_ask(user_id, &buddy).await? {
+                Some((_, true)) => return Ok(format!("{} said no, they won't be asked again", buddy.mention())),
+                Some((asked_at, _)) if asked_at + BUDDY_ASK_COOLDOWN > OffsetDateTime::now_utc() => return Ok(format!(
+                    "you already asked {} recently, you can ask again <t:{}:R>",
+                    buddy.mention(),
+                    (asked_at + BUDDY_ASK_COOLDOWN).unix_timestamp()
+                )),
+                _ => {}
+            }
+
+            let asked = bot.db.ask_buddy(user_id, &buddy, task_id, after).await?;
+            let prompt = CreateMessage::new()
+                .content(format!(
+                    "{} wants you as their accountability buddy for {}. \
+                    if they leave one unchecked for **{}**, I'll DM you so you can nudge them. ok with that?",
+                    user_id.mention(),
+                    asked.scope(),
+                    format_duration(after)
+                ))
+                .components(vec![CreateActionRow::Buttons(vec![
+                    CreateButton::new(ComponentId::AnswerBuddy { buddy: asked.id, accept: true }.encode())
+                        .label("Sure")
+                        .style(ButtonStyle::Success),
+                    CreateButton::new(ComponentId::AnswerBuddy { buddy: asked.id, accept: false }.encode())
+                        .label("No thanks")
+                        .style(ButtonStyle::Danger),
+                ])]);
+
+            let channel = bot.ctx.read().await.get_dm_channel(ctx.http.clone(), buddy).await?;
+            let outbox = bot.ctx.read().await.outbox.clone();
+            if let Err(e) = outbox.send(channel, prompt, Lane::First).await {
+                eprintln!("Failed to send buddy prompt to {buddy}: {e}");
+                bot.db.remove_buddy(user_id, task_id).await?;
+                return Ok(format!("couldn't DM {}, they may have DMs turned off", buddy.mention()));
+            }
+            // only once it went out, so they can try again after turning DMs on
+            bot.db.record_buddy_ask(user_id, &buddy).await?;
+            Ok(format!("asked {}, I'll let you know when they answer", buddy.mention()))
+        }
+    }
+}
@@ -0,0 +1,77 @@
+/// Custom ids of the message components the bot sends, encoded as `kind:arg:arg..`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ComponentId {
+    /// Ticks or unticks a checklist item of an occurrence
+    CheckItem { occurrence_id: i64, item: i32 },
+    /// One of the buttons under a reminder
+    Resolve { occurrence_id: i64, action: OccurrenceAction },
+    /// The consent prompt sent to someone asked to be a buddy
+    AnswerBuddy { buddy: i64, accept: bool },
+    /// Sends a reminder that was missed while the bot was offline
+    Redeliver { occurrence_id: i64 },
+}
+
+/// What the buttons under a reminder do to its occurrence.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OccurrenceAction {
+    Done,
+    /// Sends the reminder again a bit later
+    Snooze,
+    /// Neither done nor missed, doesn't break streaks
+    Skip,
+}
+
+impl OccurrenceAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Done => "done",
+            Self::Snooze => "snooze",
+            Self::Skip => "skip"
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "done" => Some(Self::Done),
+            "snooze" => Some(Self::Snooze),
+            "skip" => Some(Self::Skip),
+            _ => None
+        }
+    }
+}
+
+impl ComponentId {
+    pub fn encode(&self) -> String {
+        match self {
+            Self::CheckItem { occurrence_id, item } => format!("check:{occurrence_id}:{item}"),
+            Self::Resolve { occurrence_id, action } => format!("{}:{occurrence_id}", action.as_str()),
+            Self::AnswerBuddy { buddy, accept } => format!("buddy:{buddy}:{}", if *accept { "yes" } else { "no" }),
+            Self::Redeliver { occurrence_id } => format!("redeliver:{occurrence_id}"),
+        }
+    }
+
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let mut parts = custom_id.split(':');
+        match parts.next()? {
+            "check" => Some(Self::CheckItem {
+                occurrence_id: parts.next()?.parse().ok()?,
+                item: parts.next()?.parse().ok()?,
+            }),
+            "buddy" => Some(Self::AnswerBuddy {
+                buddy: parts.next()?.parse().ok()?,
+                accept: match parts.next()? {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return None
+                },
+            }),
+            "redeliver" => Some(Self::Redeliver {
+                occurrence_id: parts.next()?.parse().ok()?,
+            }),
+            other => Some(Self::Resolve {
+                action: OccurrenceAction::parse(other)?,
+                occurrence_id: parts.next()?.parse().ok()?,
+            })
+        }
+    }
+}
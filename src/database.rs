@@ -1,11 +1,14 @@
 use std::str::FromStr;
 
 use serenity::all::{ChannelId, UserId};
-use sqlx::{postgres::{PgConnectOptions, PgPool, PgPoolOptions}, query, query_as, query_scalar};
+use sqlx::{postgres::{PgConnectOptions, PgPool, PgPoolOptions}, PgConnection, query, query_as, query_scalar, types::time::OffsetDateTime};
 use anyhow::Result;
-use time::Weekday;
+use time::{Duration, Weekday};
 
-use crate::model::{Task, TaskCreateInfo, TaskRow, UserSettings, UserSettingsRow};
+/// How many actions per user can be undone.
+const ACTION_LOG_LEN: i64 = 20;
+
+use crate::model::{Action, ArchiveReason, ArchivedTask, Attachment, AttachmentRow, Buddy, BuddyCheck, BuddyRow, BuddyStatus, duration_to_millis, duration_to_secs, durations_to_secs, Occurrence, OccurrenceRow, OccurrenceStatus, Task, TaskCreateInfo, TaskRow, Template, TemplateRow, Undone, UserSettings, UserSettingsRow};
 
 pub struct Database {
     pool: PgPool
@@ -37,27 +40,46 @@ impl Database {
         })
     }
 
+    /// Defaults if the user never changed anything.
     pub async fn settings(&self, user_id: &UserId) -> Result<UserSettings> {
-        UserSettings::from_row_struct(
-            query_as!(
-                UserSettingsRow,
-                r"SELECT * FROM settings
-                where user_id = $1
-                ",
-                user_id.to_string()
-            ).fetch_one(&self.pool).await?
-        )
+        query_as!(
+            UserSettingsRow,
+            r"SELECT ack_phrase, deadline_offset_secs, nag_interval_ms, nag_start_interval_ms,
+                nag_give_up_secs, ping_mode, nag_lines
+            FROM settings
+            where user_id = $1
+            ",
+            user_id.to_string()
+        ).fetch_optional(&self.pool).await?
+        .map(UserSettings::from_row_struct)
+        .unwrap_or_else(|| Ok(UserSettings::default()))
     }
 
     pub async fn put_settings(&self, user_id: &UserId, user_settings: UserSettings) -> Result<()> {
+        let deadline_offset_secs = durations_to_secs(&user_settings.deadline_offsets)?;
         query!(
-            r"INSERT INTO settings (user_id, ack_phrase)
-            VALUES ($1, $2)
+            r"INSERT INTO settings (
+                user_id, ack_phrase, deadline_offset_secs, nag_interval_ms, nag_lines, ping_mode,
+                nag_start_interval_ms, nag_give_up_secs
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             ON CONFLICT (user_id)
             DO UPDATE SET
-            ack_phrase = EXCLUDED.ack_phrase;",
+            ack_phrase = EXCLUDED.ack_phrase,
+            deadline_offset_secs = EXCLUDED.deadline_offset_secs,
+            nag_interval_ms = EXCLUDED.nag_interval_ms,
+            nag_lines = EXCLUDED.nag_lines,
+            ping_mode = EXCLUDED.ping_mode,
+            nag_start_interval_ms = EXCLUDED.nag_start_interval_ms,
+            nag_give_up_secs = EXCLUDED.nag_give_up_secs;",
             user_id.to_string(),
-            user_settings.ack_phrase
+            user_settings.ack_phrase,
+            &deadline_offset_secs,
+            duration_to_millis(user_settings.nag.interval)?,
+            &user_settings.nag.lines,
+            user_settings.nag.mode.as_str(),
+            duration_to_millis(user_settings.nag.start_interval)?,
+            duration_to_secs(user_settings.nag.give_up_after)?
         ).execute(&self.pool).await?;
         Ok(())
     }
@@ -99,47 +121,125 @@ impl Database {
     }
 
     pub async fn add_task(&self, user_id: &UserId, task: &TaskCreateInfo) -> Result<Task> {
-        if let Some(d) = task.date {
-            Task::from_row_struct(
-                query_as!(
-                    TaskRow,
-                    r#"INSERT INTO tasks (user_id, title, info, remind_at, on_date, repeat_weekly)
-                    VALUES ($1, $2, $3, $4, $5, $6)
-                    RETURNING *"#,
-                    user_id.to_string(),
-                    task.title,
-                    task.info,
-                    task.remind_at,
-                    d,
-                    false
-                ).fetch_one(&self.pool).await?
-            )
-        } else {
-            let on_days: Vec<i32> = 
-            task.on_days.as_ref().expect("its over")
-                .iter().map(|e| e.number_from_sunday() as i32).collect::<Vec<_>>();
-            Task::from_row_struct(
-                query_as!(
-                    TaskRow,
-                    r#"INSERT INTO tasks (user_id, title, info, remind_at, on_days, repeat_weekly)
-                    VALUES ($1, $2, $3, $4, $5, $6)
-                    RETURNING *"#,
-                    user_id.to_string(),
-                    task.title,
-                    task.info,
-                    task.remind_at,
-                    &on_days,
-                    task.repeat_weekly
-                ).fetch_one(&self.pool).await?
-            )
+        let mut tx = self.pool.begin().await?;
+        let added = insert_task(&mut tx, user_id, task).await?;
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    /// Adds all of the tasks or none of them.
+    pub async fn add_tasks(&self, user_id: &UserId, tasks: &[TaskCreateInfo]) -> Result<Vec<Task>> {
+        let mut tx = self.pool.begin().await?;
+        let mut added = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            added.push(insert_task(&mut tx, user_id, task).await?);
         }
+        tx.commit().await?;
+        Ok(added)
     }
 
-    pub async fn delete_task(&self, id: i64) -> Result<()> {
+    /// Stops a task from running but keeps it and its history.
+    /// Already archived tasks keep their original reason.
+    pub async fn archive_task(&self, id: i64, reason: ArchiveReason) -> Result<()> {
         query!(
-            r"DELETE FROM tasks
+            r"UPDATE tasks
+            SET archived_at = CURRENT_TIMESTAMP, archive_reason = $2
+            WHERE id = $1 AND archived_at IS NULL",
+            id,
+            reason.as_str()
+        ).execute(&self.pool).await
+        .map_err(anyhow::Error::from)
+        .map(|_| ())
+    }
+
+    /// Puts an archived task back, unpaused. None if the user has no such archived task.
+    pub async fn restore_task(&self, user_id: &UserId, id: i64) -> Result<Option<Task>> {
+        query_as!(
+            TaskRow,
+            r"UPDATE tasks
+            SET archived_at = NULL, archive_reason = NULL, paused = false
+            WHERE id = $1 AND user_id = $2 AND archived_at IS NOT NULL
+            RETURNING *",
+            id,
+            user_id.to_string()
+        ).fetch_optional(&self.pool).await?
+        .map(Task::from_row_struct)
+        .transpose()
+    }
+
+    /// Most recently archived first.
+    pub async fn archived_tasks_for(&self, user_id: &UserId) -> Result<Vec<ArchivedTask>> {
+        query_as!(
+            TaskRow,
+            r"SELECT * FROM tasks
+            WHERE user_id = $1 AND archived_at IS NOT NULL
+            ORDER BY archived_at DESC
+            ",
+            user_id.to_string()
+        ).fetch_all(&self.pool).await?
+        .into_iter().map(ArchivedTask::from_row_struct).collect()
+    }
+
+    /// Updates a task to the new title, info and time, keeping its id and history.
+    pub async fn update_task(&self, id: i64, task: &TaskCreateInfo) -> Result<Task> {
+        let on_days: Option<Vec<i32>> = task.on_days.as_ref()
+            .map(|days| days.iter().map(|e| e.number_from_sunday() as i32).collect());
+        let (field_names, field_values): (Vec<_>, Vec<_>) = task.style.fields.iter().cloned().unzip();
+        let mut tx = self.pool.begin().await?;
+        let updated = Task::from_row_struct(
+            query_as!(
+                TaskRow,
+                // recurring tasks count their first week from time_created
+                r#"UPDATE tasks
+                SET kind = $2, title = $3, info = $4, remind_at = $5, on_date = $6, on_days = $7,
+                    repeat_weekly = $8, lead_secs = $9, tags = $10, priority = $11, offset_secs = $12,
+                    colour = $13, url = $14, thumbnail = $15, field_names = $16, field_values = $17,
+                    after_task = $18, delay_secs = $19, challenge = $20, fire_at = NULL, time_created = CURRENT_TIMESTAMP
+                WHERE id = $1
+                RETURNING *"#,
+                id,
+                task.kind.as_str(),
+                task.title,
+                task.info,
+                task.remind_at,
+                task.date,
+                on_days.as_deref(),
+                task.repeat_weekly,
+                &durations_to_secs(&task.lead_times)?,
+                &task.tags,
+                task.priority.as_str(),
+                &durations_to_secs(&task.offsets)?,
+                task.style.colour.map(|c| c as i32),
+                task.style.url,
+                task.style.thumbnail,
+                &field_names,
+                &field_values,
+                task.after_task,
+                task.after_task.map(|_| duration_to_secs(task.delay)).transpose()?,
+                task.challenge.map(|c| c.as_str())
+            ).fetch_one(&mut *tx).await?
+        )?;
+        if !task.attachments.is_empty() {
+            // not deleted, undoing the edit brings them back
+            query!(
+                r"UPDATE attachments
+                SET replaced_at = CURRENT_TIMESTAMP
+                WHERE task_id = $1 AND replaced_at IS NULL",
+                id
+            ).execute(&mut *tx).await?;
+            insert_attachments(&mut tx, id, &task.attachments).await?;
+        }
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    pub async fn set_paused(&self, id: i64, paused: bool) -> Result<()> {
+        query!(
+            r"UPDATE tasks
+            SET paused = $2
             WHERE id = $1",
-            id
+            id,
+            paused
         ).execute(&self.pool).await
         .map_err(anyhow::Error::from)
         .map(|_| ())
@@ -150,7 +250,8 @@ impl Database {
             query_as!(
                 TaskRow,
                 r"SELECT * FROM tasks
-                where user_id = $1
+                where user_id = $1 AND archived_at IS NULL
+                ORDER BY id
                 ",
                 user_id.to_string()
             ).fetch_all(&self.pool).await?
@@ -163,9 +264,728 @@ impl Database {
             query_as!(
                 TaskRow,
                 r"SELECT * FROM tasks
+                WHERE archived_at IS NULL
                 ",
             ).fetch_all(&self.pool).await?
             .into_iter().map(|t| Task::from_row_struct(t)).collect::<Result<Vec<Task>>>()?
         )
     }
-}
\ No newline at end of file
+
+    pub async fn attachments_for(&self, task_id: i64) -> Result<Vec<Attachment>> {
+        Ok(
+            query_as!(
+                AttachmentRow,
+                r"SELECT filename, content_type, data FROM attachments
+                WHERE task_id = $1 AND replaced_at IS NULL
+                ORDER BY id",
+                task_id
+            ).fetch_all(&self.pool).await?
+            .into_iter().map(Attachment::from_row_struct).collect()
+        )
+    }
+
+    /// Saves the tasks as they are right now, so the action can be undone.
+    /// Call it before changing them, or after creating them.
+    /// Nothing is logged without tasks, an empty entry would hide the action before it.
+    pub async fn log_action(&self, user_id: &UserId, action: Action, task_ids: &[i64]) -> Result<()> {
+        if task_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        // the files are referenced by id, replaced ones stick around while an entry points at them
+        query!(
+            r"INSERT INTO action_log (user_id, action, tasks)
+            SELECT $1, $2, COALESCE(jsonb_agg(to_jsonb(t) || jsonb_build_object(
+                'attachment_ids', ARRAY(
+                    SELECT id FROM attachments
+                    WHERE task_id = t.id AND replaced_at IS NULL
+                )
+            )), '[]')
+            FROM tasks t
+            WHERE id = ANY($3)",
+            user_id.to_string(),
+            action.as_str(),
+            task_ids
+        ).execute(&mut *tx).await?;
+        // only keep the latest few
+        query!(
+            r"DELETE FROM action_log
+            WHERE user_id = $1 AND id NOT IN (
+                SELECT id FROM action_log
+                WHERE user_id = $1
+                ORDER BY id DESC
+                LIMIT $2
+            )",
+            user_id.to_string(),
+            ACTION_LOG_LEN
+        ).execute(&mut *tx).await?;
+        forget_replaced_attachments(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Reverses the user's latest action in the database and removes it from the log.
+    /// Deleted or changed tasks are put back the way they were, created ones are archived as cancelled.
+    /// Their reminder jobs are left to the caller.
+    pub async fn undo(&self, user_id: &UserId) -> Result<Option<Undone>> {
+        let mut tx = self.pool.begin().await?;
+        let Some(entry) = query!(
+            r"SELECT id, action FROM action_log
+            WHERE user_id = $1
+            ORDER BY id DESC
+            LIMIT 1",
+            user_id.to_string()
+        ).fetch_optional(&mut *tx).await? else {
+            return Ok(None);
+        };
+        let action = Action::parse(&entry.action)?;
+
+        let rows = if action == Action::Create {
+            // archived like a delete, so the history stays and it can be restored
+            query_as!(
+                TaskRow,
+                r"UPDATE tasks
+                SET archived_at = CURRENT_TIMESTAMP, archive_reason = $2
+                WHERE archived_at IS NULL AND id IN (
+                    SELECT (jsonb_array_elements(tasks)->>'id')::BIGINT
+                    FROM action_log
+                    WHERE id = $1
+                )
+                RETURNING *",
+                entry.id,
+                ArchiveReason::Cancelled.as_str()
+            ).fetch_all(&mut *tx).await?
+        } else {
+            query_as!(
+                TaskRow,
+                r"INSERT INTO tasks
+                SELECT r.* FROM action_log l, jsonb_populate_recordset(NULL::tasks, l.tasks) r
+                WHERE l.id = $1
+                ON CONFLICT (id) DO UPDATE
+                SET (
+                    user_id, title, info, time_created, remind_at, on_date, on_days, repeat_weekly,
+                    lead_secs, tags, paused, priority, kind, offset_secs, archived_at, archive_reason,
+                    colour, url, thumbnail, field_names, field_values, after_task, delay_secs, fire_at,
+                    challenge
+                ) = ROW(
+                    EXCLUDED.user_id, EXCLUDED.title, EXCLUDED.info, EXCLUDED.time_created, 
+                    EXCLUDED.remind_at, EXCLUDED.on_date, EXCLUDED.on_days, EXCLUDED.repeat_weekly,
+                    EXCLUDED.lead_secs, EXCLUDED.tags, EXCLUDED.paused, EXCLUDED.priority, 
+                    EXCLUDED.kind, EXCLUDED.offset_secs, EXCLUDED.archived_at, EXCLUDED.archive_reason,
+                    EXCLUDED.colour, EXCLUDED.url, EXCLUDED.thumbnail, EXCLUDED.field_names, EXCLUDED.field_values,
+                    EXCLUDED.after_task, EXCLUDED.delay_secs, EXCLUDED.fire_at,
+                    EXCLUDED.challenge
+                )
+                RETURNING *",
+                entry.id
+            ).fetch_all(&mut *tx).await?
+        };
+        // an edit may have replaced the files, put back the ones the task had before
+        query!(
+            r"WITH snapshot AS (
+                SELECT (t->>'id')::BIGINT AS task_id, t->'attachment_ids' AS ids
+                FROM action_log l, jsonb_array_elements(l.tasks) t
+                WHERE l.id = $1 AND t ? 'attachment_ids'
+            )
+            UPDATE attachments a
+            SET replaced_at = CASE
+                WHEN s.ids @> to_jsonb(a.id) THEN NULL
+                ELSE COALESCE(a.replaced_at, CURRENT_TIMESTAMP)
+            END
+            FROM snapshot s
+            WHERE a.task_id = s.task_id",
+            entry.id
+        ).execute(&mut *tx).await?;
+
+        query!(
+            r"DELETE FROM action_log
+            WHERE id = $1",
+            entry.id
+        ).execute(&mut *tx).await?;
+        forget_replaced_attachments(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(Some(Undone {
+            action,
+            tasks: rows.into_iter().map(Task::from_row_struct).collect::<Result<Vec<Task>>>()?
+        }))
+    }
+
+    /// Waits for running queries to finish, then closes every connection.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Records a new pending occurrence of a task.
+    /// Any older occurrence of the same task that is still pending was never
+    /// acknowledged, so it gets marked as missed.
+    pub async fn add_occurrence(
+        &self, 
+        task_id: i64, 
+        user_id: &UserId, 
+        due_at: OffsetDateTime,
+        checklist_len: i32
+    ) -> Result<Occurrence> {
+        let mut tx = self.pool.begin().await?;
+
+        query!(
+            r"UPDATE occurrences
+            SET status = $2, resolved_at = CURRENT_TIMESTAMP
+            WHERE task_id = $1 AND status = $3",
+            task_id,
+            OccurrenceStatus::Missed.as_str(),
+            OccurrenceStatus::Pending.as_str()
+        ).execute(&mut *tx).await?;
+
+        let row = query_as!(
+            OccurrenceRow,
+            r"INSERT INTO occurrences (task_id, user_id, due_at, status, checklist_len)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, task_id, due_at, status, checklist_len, snoozed_until, nag_started_at",
+            task_id,
+            user_id.to_string(),
+            due_at,
+            OccurrenceStatus::Pending.as_str(),
+            checklist_len
+        ).fetch_one(&mut *tx).await?;
+
+        tx.commit().await?;
+        Occurrence::from_row_struct(row)
+    }
+
+    pub async fn occurrence(&self, id: i64) -> Result<Occurrence> {
+        Occurrence::from_row_struct(
+            query_as!(
+                OccurrenceRow,
+                r"SELECT id, task_id, due_at, status, checklist_len, snoozed_until, nag_started_at FROM occurrences
+                WHERE id = $1",
+                id
+            ).fetch_one(&self.pool).await?
+        )
+    }
+
+    /// Oldest first.
+    pub async fn occurrences_for(&self, task_id: i64) -> Result<Vec<Occurrence>> {
+        query_as!(
+            OccurrenceRow,
+            r"SELECT id, task_id, due_at, status, checklist_len, snoozed_until, nag_started_at FROM occurrences
+            WHERE task_id = $1
+            ORDER BY due_at ASC",
+            task_id
+        ).fetch_all(&self.pool).await?
+        .into_iter().map(Occurrence::from_row_struct).collect()
+    }
+
+    /// Returns the id of the occurrence's task.
+    pub async fn complete_occurrence(&self, id: i64) -> Result<i64> {
+        Ok(
+            query_scalar!(
+                r#"WITH done AS (
+                    UPDATE occurrences
+                    SET status = $2, resolved_at = CURRENT_TIMESTAMP
+                    WHERE id = $1
+                    RETURNING task_id
+                ), completed AS (
+                    UPDATE tasks
+                    SET archive_reason = $3
+                    WHERE archive_reason = $4 AND id IN (SELECT task_id FROM done)
+                )
+                SELECT task_id AS "task_id!" FROM done"#,
+                id,
+                OccurrenceStatus::Done.as_str(),
+                ArchiveReason::Completed.as_str(),
+                ArchiveReason::Expired.as_str()
+            ).fetch_one(&self.pool).await?
+        )
+    }
+
+    /// For when nagging gives up. Left alone if it was resolved in the meantime.
+    pub async fn miss_occurrence(&self, id: i64) -> Result<()> {
+        query!(
+            r"UPDATE occurrences
+            SET status = $2, resolved_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND status = $3",
+            id,
+            OccurrenceStatus::Missed.as_str(),
+            OccurrenceStatus::Pending.as_str()
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn skip_occurrence(&self, id: i64) -> Result<()> {
+        query!(
+            r"UPDATE occurrences
+            SET status = $2, resolved_at = CURRENT_TIMESTAMP
+            WHERE id = $1",
+            id,
+            OccurrenceStatus::Skipped.as_str()
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn snooze_occurrence(&self, id: i64, until: OffsetDateTime) -> Result<()> {
+        query!(
+            r"UPDATE occurrences
+            SET snoozed_until = $2
+            WHERE id = $1",
+            id,
+            until
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// The snooze is over once the reminder has been sent again.
+    pub async fn end_snooze(&self, id: i64) -> Result<()> {
+        query!(
+            r"UPDATE occurrences
+            SET snoozed_until = NULL
+            WHERE id = $1",
+            id
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// None once the nag is over.
+    pub async fn set_nag_started(&self, id: i64, started: Option<OffsetDateTime>) -> Result<()> {
+        query!(
+            r"UPDATE occurrences
+            SET nag_started_at = $2
+            WHERE id = $1",
+            id,
+            started
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Pending occurrences that were being nagged about or snoozed, for picking up after a restart.
+    pub async fn interrupted_occurrences(&self) -> Result<Vec<Occurrence>> {
+        query_as!(
+            OccurrenceRow,
+            r"SELECT id, task_id, due_at, status, checklist_len, snoozed_until, nag_started_at FROM occurrences
+            WHERE status = $1 AND (nag_started_at IS NOT NULL OR snoozed_until IS NOT NULL)
+            ORDER BY due_at ASC",
+            OccurrenceStatus::Pending.as_str()
+        ).fetch_all(&self.pool).await?
+        .into_iter().map(Occurrence::from_row_struct).collect()
+    }
+
+    /// Records a time the task was due while the bot was down, unless it was sent after all.
+    /// Like [`Database::add_occurrence`], older pending occurrences count as missed by now.
+    pub async fn add_missed_occurrence(
+        &self, 
+        task_id: i64, 
+        user_id: &UserId, 
+        due_at: OffsetDateTime,
+        checklist_len: i32
+    ) -> Result<Option<Occurrence>> {
+        let mut tx = self.pool.begin().await?;
+
+        query!(
+            r"UPDATE occurrences
+            SET status = $3, resolved_at = CURRENT_TIMESTAMP
+            WHERE task_id = $1 AND due_at < $2 AND status = $4",
+            task_id,
+            due_at,
+            OccurrenceStatus::Missed.as_str(),
+            OccurrenceStatus::Pending.as_str()
+        ).execute(&mut *tx).await?;
+
+        let row = query_as!(
+            OccurrenceRow,
+            r"INSERT INTO occurrences (task_id, user_id, due_at, status, resolved_at, checklist_len)
+            SELECT $1, $2, $3, $4, CURRENT_TIMESTAMP, $5
+            WHERE NOT EXISTS (SELECT 1 FROM occurrences WHERE task_id = $1 AND due_at = $3)
+            RETURNING id, task_id, due_at, status, checklist_len, snoozed_until, nag_started_at",
+            task_id,
+            user_id.to_string(),
+            due_at,
+            OccurrenceStatus::Missed.as_str(),
+            checklist_len
+        ).fetch_optional(&mut *tx).await?;
+
+        tx.commit().await?;
+        row.map(Occurrence::from_row_struct).transpose()
+    }
+
+    /// Makes a missed occurrence pending again for sending it late.
+    /// None if it isn't the user's or isn't missed anymore.
+    pub async fn reopen_occurrence(&self, id: i64, user_id: &UserId) -> Result<Option<Occurrence>> {
+        query_as!(
+            OccurrenceRow,
+            r"UPDATE occurrences
+            SET status = $3, resolved_at = NULL
+            WHERE id = $1 AND user_id = $2 AND status = $4
+            RETURNING id, task_id, due_at, status, checklist_len, snoozed_until, nag_started_at",
+            id,
+            user_id.to_string(),
+            OccurrenceStatus::Pending.as_str(),
+            OccurrenceStatus::Missed.as_str()
+        ).fetch_optional(&self.pool).await?
+        .map(Occurrence::from_row_struct)
+        .transpose()
+    }
+
+    /// When the bot last checked in, None the first time it runs.
+    pub async fn last_seen(&self) -> Result<Option<OffsetDateTime>> {
+        Ok(
+            query_scalar!(
+                r"SELECT last_seen FROM heartbeat"
+            ).fetch_optional(&self.pool).await?
+        )
+    }
+
+    pub async fn beat(&self) -> Result<()> {
+        query!(
+            r"INSERT INTO heartbeat (last_seen)
+            VALUES (CURRENT_TIMESTAMP)
+            ON CONFLICT (id)
+            DO UPDATE SET last_seen = EXCLUDED.last_seen"
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Sets off the tasks chained after any of `task_ids`, returning them.
+    pub async fn arm_chains(&self, task_ids: &[i64]) -> Result<Vec<Task>> {
+        query_as!(
+            TaskRow,
+            r"UPDATE tasks
+            SET fire_at = CURRENT_TIMESTAMP + make_interval(secs => delay_secs)
+            WHERE after_task = ANY($1) AND archived_at IS NULL
+            RETURNING *",
+            task_ids
+        ).fetch_all(&self.pool).await?
+        .into_iter().map(Task::from_row_struct).collect()
+    }
+
+    /// False once the task is archived and has nothing left to acknowledge.
+    pub async fn can_set_off_chains(&self, task_id: i64) -> Result<bool> {
+        Ok(
+            query_scalar!(
+                r#"SELECT (
+                    EXISTS (
+                        SELECT 1 FROM tasks
+                        WHERE id = $1 AND archived_at IS NULL
+                    ) OR EXISTS (
+                        SELECT 1 FROM occurrences
+                        WHERE task_id = $1 AND status = $2
+                    )
+                ) AS "can!""#,
+                task_id,
+                OccurrenceStatus::Pending.as_str()
+            ).fetch_one(&self.pool).await?
+        )
+    }
+
+    pub async fn checked_items(&self, occurrence_id: i64) -> Result<Vec<i32>> {
+        query_scalar!(
+            r"SELECT item FROM checklist_progress
+            WHERE occurrence_id = $1
+            ORDER BY item",
+            occurrence_id
+        ).fetch_all(&self.pool).await
+        .map_err(anyhow::Error::from)
+    }
+
+    /// Ticks the item if it isn't ticked yet, otherwise unticks it.
+    /// Returns the ticked items afterwards.
+    pub async fn toggle_checklist_item(&self, occurrence_id: i64, item: i32) -> Result<Vec<i32>> {
+        let unticked = query!(
+            r"DELETE FROM checklist_progress
+            WHERE occurrence_id = $1 AND item = $2",
+            occurrence_id,
+            item
+        ).execute(&self.pool).await?.rows_affected();
+
+        if unticked == 0 {
+            query!(
+                r"INSERT INTO checklist_progress (occurrence_id, item)
+                VALUES ($1, $2)
+                ON CONFLICT DO NOTHING",
+                occurrence_id,
+                item
+            ).execute(&self.pool).await?;
+        }
+
+        self.checked_items(occurrence_id).await
+    }
+
+    /// Replaces the user's buddy for the task (or every task if None),
+    /// the new one has to accept first.
+    pub async fn ask_buddy(&self, user_id: &UserId, buddy_id: &UserId, task_id: Option<i64>, after: Duration) -> Result<Buddy> {
+        Buddy::from_row_struct(
+            query_as!(
+                BuddyRow,
+                r"INSERT INTO buddies (user_id, buddy_id, task_id, after_secs, status)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (user_id, task_id)
+                DO UPDATE SET
+                buddy_id = EXCLUDED.buddy_id,
+                after_secs = EXCLUDED.after_secs,
+                status = EXCLUDED.status
+                RETURNING *",
+                user_id.to_string(),
+                buddy_id.to_string(),
+                task_id,
+                duration_to_secs(after)?,
+                BuddyStatus::Asked.as_str()
+            ).fetch_one(&self.pool).await?
+        )
+    }
+
+    /// None if the buddy wasn't asked, or already answered.
+    pub async fn answer_buddy(&self, id: i64, buddy_id: &UserId, accept: bool) -> Result<Option<Buddy>> {
+        let status = if accept { BuddyStatus::Accepted } else { BuddyStatus::Declined };
+        let mut tx = self.pool.begin().await?;
+        let Some(row) = query_as!(
+            BuddyRow,
+            r"UPDATE buddies
+            SET status = $3
+            WHERE id = $1 AND buddy_id = $2 AND status = $4
+            RETURNING *",
+            id,
+            buddy_id.to_string(),
+            status.as_str(),
+            BuddyStatus::Asked.as_str()
+        ).fetch_optional(&mut *tx).await? else {
+            return Ok(None);
+        };
+        if !accept {
+            // the prompt may be answered before the ask is recorded
+            query!(
+                r"INSERT INTO buddy_asks (user_id, buddy_id, last_asked_at, declined)
+                VALUES ($1, $2, CURRENT_TIMESTAMP, TRUE)
+                ON CONFLICT (user_id, buddy_id)
+                DO UPDATE SET declined = TRUE",
+                row.user_id,
+                row.buddy_id
+            ).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Buddy::from_row_struct(row).map(Some)
+    }
+
+    /// Notes that the user sent the buddy a prompt.
+    pub async fn record_buddy_ask(&self, user_id: &UserId, buddy_id: &UserId) -> Result<()> {
+        query!(
+            r"INSERT INTO buddy_asks (user_id, buddy_id, last_asked_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP)
+            ON CONFLICT (user_id, buddy_id)
+            DO UPDATE SET last_asked_at = EXCLUDED.last_asked_at",
+            user_id.to_string(),
+            buddy_id.to_string()
+        ).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// When the user last asked the buddy, and whether they said no. None if they never did.
+    pub async fn last_buddy_ask(&self, user_id: &UserId, buddy_id: &UserId) -> Result<Option<(OffsetDateTime, bool)>> {
+        Ok(
+            query!(
+                r"SELECT last_asked_at, declined FROM buddy_asks
+                WHERE user_id = $1 AND buddy_id = $2",
+                user_id.to_string(),
+                buddy_id.to_string()
+            ).fetch_optional(&self.pool).await?
+            .map(|r| (r.last_asked_at, r.declined))
+        )
+    }
+
+    /// The one for every task first.
+    pub async fn buddies_for(&self, user_id: &UserId) -> Result<Vec<Buddy>> {
+        query_as!(
+            BuddyRow,
+            r"SELECT * FROM buddies
+            WHERE user_id = $1
+            ORDER BY task_id NULLS FIRST",
+            user_id.to_string()
+        ).fetch_all(&self.pool).await?
+        .into_iter().map(Buddy::from_row_struct).collect()
+    }
+
+    /// Returns false if there was no such buddy.
+    pub async fn remove_buddy(&self, user_id: &UserId, task_id: Option<i64>) -> Result<bool> {
+        Ok(
+            query!(
+                r"DELETE FROM buddies
+                WHERE user_id = $1 AND task_id IS NOT DISTINCT FROM $2",
+                user_id.to_string(),
+                task_id
+            ).execute(&self.pool).await?
+            .rows_affected() > 0
+        )
+    }
+
+    /// Pending occurrences with an accepted buddy who hasn't been told yet,
+    /// all of them or just the one with `occurrence_id`.
+    /// A task's own buddy comes before the user's buddy for every task.
+    pub async fn buddy_checks(&self, occurrence_id: Option<i64>) -> Result<Vec<BuddyCheck>> {
+        query!(
+            r#"SELECT o.id, o.user_id, b.buddy_id, t.title, o.due_at,
+                o.due_at + make_interval(secs => b.after_secs) AS "tell_at!"
+            FROM occurrences o
+            JOIN tasks t ON t.id = o.task_id
+            JOIN LATERAL (
+                SELECT * FROM buddies
+                WHERE user_id = o.user_id AND status = $1
+                    AND (task_id = o.task_id OR task_id IS NULL)
+                ORDER BY task_id NULLS LAST
+                LIMIT 1
+            ) b ON true
+            WHERE o.status = $2 AND o.buddy_told_at IS NULL
+                AND ($3::BIGINT IS NULL OR o.id = $3)"#,
+            BuddyStatus::Accepted.as_str(),
+            OccurrenceStatus::Pending.as_str(),
+            occurrence_id
+        ).fetch_all(&self.pool).await?
+        .into_iter()
+        .map(|row| Ok(BuddyCheck {
+            occurrence_id: row.id,
+            user_id: UserId::new(row.user_id.parse::<u64>()?),
+            buddy_id: UserId::new(row.buddy_id.parse::<u64>()?),
+            title: row.title,
+            due_at: row.due_at,
+            tell_at: row.tell_at
+        }))
+        .collect()
+    }
+
+    /// Returns false if the occurrence was resolved or the buddy was told already.
+    pub async fn mark_buddy_told(&self, occurrence_id: i64) -> Result<bool> {
+        Ok(
+            query!(
+                r"UPDATE occurrences
+                SET buddy_told_at = CURRENT_TIMESTAMP
+                WHERE id = $1 AND status = $2 AND buddy_told_at IS NULL",
+                occurrence_id,
+                OccurrenceStatus::Pending.as_str()
+            ).execute(&self.pool).await?
+            .rows_affected() > 0
+        )
+    }
+
+    /// Overwrites any template with the same name.
+    pub async fn put_template(&self, user_id: &UserId, template: &Template) -> Result<()> {
+        query!(
+            r"INSERT INTO templates (user_id, name, title, info, time_line)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id, name)
+            DO UPDATE SET
+            title = EXCLUDED.title,
+            info = EXCLUDED.info,
+            time_line = EXCLUDED.time_line;",
+            user_id.to_string(),
+            template.name,
+            template.title,
+            template.info,
+            template.time_line
+        ).execute(&self.pool).await
+        .map_err(anyhow::Error::from)
+        .map(|_| ())
+    }
+
+    pub async fn template(&self, user_id: &UserId, name: &str) -> Result<Option<Template>> {
+        Ok(
+            query_as!(
+                TemplateRow,
+                r"SELECT name, title, info, time_line FROM templates
+                WHERE user_id = $1 AND name = $2",
+                user_id.to_string(),
+                name
+            ).fetch_optional(&self.pool).await?
+            .map(Template::from_row_struct)
+        )
+    }
+
+    pub async fn templates_for(&self, user_id: &UserId) -> Result<Vec<Template>> {
+        Ok(
+            query_as!(
+                TemplateRow,
+                r"SELECT name, title, info, time_line FROM templates
+                WHERE user_id = $1
+                ORDER BY name",
+                user_id.to_string()
+            ).fetch_all(&self.pool).await?
+            .into_iter().map(Template::from_row_struct).collect()
+        )
+    }
+
+    /// Returns false if there was no such template.
+    pub async fn delete_template(&self, user_id: &UserId, name: &str) -> Result<bool> {
+        Ok(
+            query!(
+                r"DELETE FROM templates
+                WHERE user_id = $1 AND name = $2",
+                user_id.to_string(),
+                name
+            ).execute(&self.pool).await?
+            .rows_affected() > 0
+        )
+    }
+}
+
+async fn insert_task(conn: &mut PgConnection, user_id: &UserId, task: &TaskCreateInfo) -> Result<Task> {
+    let (field_names, field_values): (Vec<_>, Vec<_>) = task.style.fields.iter().cloned().unzip();
+    let on_days: Option<Vec<i32>> = task.on_days.as_ref()
+        .map(|days| days.iter().map(|e| e.number_from_sunday() as i32).collect());
+    let added = Task::from_row_struct(
+        query_as!(
+            TaskRow,
+            r#"INSERT INTO tasks (
+                kind, user_id, title, info, remind_at, on_date, on_days, repeat_weekly, 
+                lead_secs, tags, priority, offset_secs, colour, url, thumbnail, field_names, field_values,
+                after_task, delay_secs, challenge
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)
+            RETURNING *"#,
+            task.kind.as_str(),
+            user_id.to_string(),
+            task.title,
+            task.info,
+            task.remind_at,
+            task.date,
+            on_days.as_deref(),
+            task.repeat_weekly,
+            &durations_to_secs(&task.lead_times)?,
+            &task.tags,
+            task.priority.as_str(),
+            &durations_to_secs(&task.offsets)?,
+            task.style.colour.map(|c| c as i32),
+            task.style.url,
+            task.style.thumbnail,
+            &field_names,
+            &field_values,
+            task.after_task,
+            task.after_task.map(|_| duration_to_secs(task.delay)).transpose()?,
+            task.challenge.map(|c| c.as_str())
+        ).fetch_one(&mut *conn).await?
+    )?;
+    insert_attachments(conn, added.id(), &task.attachments).await?;
+    Ok(added)
+}
+
+/// Deletes replaced files once no undo entry can bring them back.
+async fn forget_replaced_attachments(conn: &mut PgConnection) -> Result<()> {
+    query!(
+        r"DELETE FROM attachments
+        WHERE replaced_at IS NOT NULL AND id NOT IN (
+            SELECT ids.id::BIGINT
+            FROM action_log l, jsonb_array_elements(l.tasks) t, jsonb_array_elements_text(t->'attachment_ids') ids(id)
+        )"
+    ).execute(&mut *conn).await?;
+    Ok(())
+}
+
+async fn insert_attachments(conn: &mut PgConnection, task_id: i64, attachments: &[Attachment]) -> Result<()> {
+    for attachment in attachments {
+        query!(
+            r"INSERT INTO attachments (task_id, filename, content_type, data)
+            VALUES ($1, $2, $3, $4)",
+            task_id,
+            attachment.filename,
+            attachment.content_type,
+            attachment.data
+        ).execute(&mut *conn).await?;
+    }
+    Ok(())
+}
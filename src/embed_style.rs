@@ -0,0 +1,148 @@
+use serenity::all::{Colour, CreateEmbed};
+
+use crate::checklist;
+
+/// Discord embed limits, in characters.
+const MAX_TITLE_LEN: usize = 256;
+const MAX_DESCRIPTION_LEN: usize = 4096;
+const MAX_FIELDS: usize = 25;
+const MAX_FIELD_NAME_LEN: usize = 256;
+const MAX_FIELD_VALUE_LEN: usize = 1024;
+const MAX_TOTAL_LEN: usize = 6000;
+/// Kept free for what gets added when the reminder is sent,
+/// like the deadline line and the stats footer.
+const RESERVED_LEN: usize = 200;
+
+const DEFAULT_COLOUR: Colour = Colour::from_rgb(255, 255, 255);
+
+/// How a task's reminder embed looks, besides the title and info.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmbedStyle {
+    pub colour: Option<u32>,
+    /// Makes the title a link
+    pub url: Option<String>,
+    pub thumbnail: Option<String>,
+    /// (name, value)
+    pub fields: Vec<(String, String)>,
+}
+
+impl EmbedStyle {
+    /// Takes the `key: value` lines out of the info and returns the rest of it.
+    /// `colour`, `url` and `thumbnail` set those, any other key becomes a field.
+    pub fn extract(info: &str) -> Result<(Self, String), String> {
+        let mut style = Self::default();
+        let mut rest = vec![];
+
+        for line in info.lines() {
+            let Some((key, value)) = key_value(line) else {
+                rest.push(line);
+                continue;
+            };
+            match key.to_lowercase().as_str() {
+                "colour" | "color" => style.colour = Some(parse_colour(value)?),
+                "url" => style.url = Some(parse_url(value)?),
+                "thumbnail" => style.thumbnail = Some(parse_url(value)?),
+                _ => style.fields.push((key.into(), value.into()))
+            }
+        }
+
+        Ok((style, rest.join("\n")))
+    }
+
+    /// Checks the reminder embed fits in discord's limits,
+    /// so it isn't rejected when it's finally sent.
+    pub fn validate(&self, title: &str, info: &str) -> Result<(), String> {
+        let title_len = title.chars().count();
+        if title_len > MAX_TITLE_LEN {
+            return Err(format!("the title is {title_len} characters long, it can be at most {MAX_TITLE_LEN}"));
+        }
+
+        let description_len = checklist::render(info, &[]).chars().count();
+        if description_len > MAX_DESCRIPTION_LEN - RESERVED_LEN {
+            return Err(format!(
+                "the info is {description_len} characters long, it can be at most {}",
+                MAX_DESCRIPTION_LEN - RESERVED_LEN
+            ));
+        }
+
+        if self.fields.len() > MAX_FIELDS {
+            return Err(format!("there are {} `key: value` fields, there can be at most {MAX_FIELDS}", self.fields.len()));
+        }
+        let mut total = title_len + description_len;
+        for (name, value) in &self.fields {
+            let (name_len, value_len) = (name.chars().count(), value.chars().count());
+            if name_len > MAX_FIELD_NAME_LEN {
+                return Err(format!("the field name `{name}` is too long, it can be at most {MAX_FIELD_NAME_LEN} characters"));
+            }
+            if value_len > MAX_FIELD_VALUE_LEN {
+                return Err(format!("the `{name}` field is too long, it can be at most {MAX_FIELD_VALUE_LEN} characters"));
+            }
+            total += name_len + value_len;
+        }
+
+        if total > MAX_TOTAL_LEN - RESERVED_LEN {
+            return Err(format!(
+                "the reminder is {total} characters long in total, it can be at most {}",
+                MAX_TOTAL_LEN - RESERVED_LEN
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn apply(&self, mut embed: CreateEmbed) -> CreateEmbed {
+        embed = embed.color(self.colour.map(Colour::new).unwrap_or(DEFAULT_COLOUR));
+        if let Some(url) = &self.url {
+            embed = embed.url(url);
+        }
+        if let Some(thumbnail) = &self.thumbnail {
+            embed = embed.thumbnail(thumbnail);
+        }
+        embed.fields(self.fields.iter().map(|(name, value)| (name, value, true)))
+    }
+}
+
+/// `key: value`, where the key is a few words at most so normal sentences with a colon stay in the info.
+fn key_value(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(": ")?;
+    let (key, value) = (key.trim(), value.trim());
+    let is_field = !key.is_empty()
+        && !value.is_empty()
+        // checklist items
+        && !key.starts_with('-')
+        && key.split_whitespace().count() <= 3;
+    is_field.then_some((key, value))
+}
+
+fn parse_colour(value: &str) -> Result<u32, String> {
+    let named = match value.to_lowercase().as_str() {
+        "red" => Some(0xe74c3c),
+        "orange" => Some(0xe67e22),
+        "yellow" => Some(0xf1c40f),
+        "green" => Some(0x2ecc71),
+        "blue" => Some(0x3498db),
+        "purple" => Some(0x9b59b6),
+        "pink" => Some(0xff69b4),
+        "white" => Some(0xffffff),
+        "gray" | "grey" => Some(0x95a5a6),
+        "black" => Some(0x000000),
+        _ => None
+    };
+    if let Some(colour) = named {
+        return Ok(colour);
+    }
+
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("`{value}` isn't a colour, try `#ff8800` or `orange`"));
+    }
+    u32::from_str_radix(hex, 16)
+        .map_err(|_| format!("`{value}` isn't a colour, try `#ff8800` or `orange`"))
+}
+
+fn parse_url(value: &str) -> Result<String, String> {
+    if !(value.starts_with("https://") || value.starts_with("http://")) {
+        return Err(format!("`{value}` isn't a link, it has to start with https://"));
+    }
+    Ok(value.into())
+}
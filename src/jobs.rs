@@ -2,102 +2,258 @@ use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use chrono::Offset;
 use itertools::Itertools;
-use serenity::all::{ChannelId, Colour, CreateEmbed, CreateMessage, Http, Mentionable, UserId};
+use serenity::all::{ButtonStyle, ChannelId, Colour, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, CreateMessage, EditMessage, Http, Mentionable, Message, MessageFlags, UserId};
 use ::time::{Date, OffsetDateTime, Weekday};
 use tokio::{sync::{watch, Mutex}, time::{self, Instant, Sleep}};
 use anyhow::Result;
 
-use crate::{bot::DzContext, database::Database, model::{Task, TaskRemindInfo}};
+use crate::{attachments, bot::DzContext, challenge::Challenge, checklist, components::{ComponentId, OccurrenceAction}, database::Database, outbox::{Lane, Outbox}, model::{ArchiveReason, Attachment, BuddyCheck, NagCadence, PingMode, Occurrence, OccurrenceStatus, Priority, Task, TaskRemindInfo}, stats::HabitStats, time_parse::format_duration};
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-pub enum SpamPingSignal {
-    Start,
-    Stop,
-    End
-}
+/// How long the snooze button puts a reminder off for.
+pub const SNOOZE: ::time::Duration = ::time::Duration::minutes(10);
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-pub enum SpamPingStatus {
-    Active,
-    Stopped,
+/// Pings the user about one fired reminder until it's acknowledged or nagging gives up.
+/// Dropping the job stops the pings, so removing it from `nags_ctl` is all it takes.
+pub struct NagJob {
+    pub user_id: UserId,
+    pub task_id: i64,
+    pub title: String,
+    /// Has to be answered instead of saying the ack phrase
+    pub challenge: Option<Challenge>,
+    to_task: watch::Sender<()>,
+    cadence: watch::Sender<NagCadence>,
 }
 
-pub struct SpamPingJob {
-    to_task: watch::Sender<SpamPingSignal>,
-    from_task: watch::Receiver<SpamPingStatus>,
-}
+impl NagJob {
+    pub fn new(
+        ctx: DzContext, 
+        http: Arc<Http>, 
+        occurrence_id: i64, 
+        task_id: i64,
+        task_info: &TaskRemindInfo,
+        cadence: NagCadence,
+        started: OffsetDateTime
+    ) -> Self {
+        let user_id = task_info.user_id;
+        let (to_task, mut from_ctl) = watch::channel(());
+        let (cadence_tx, cadence_rx) = watch::channel(cadence);
+        let title = task_info.title.clone();
+        let challenge = task_info.challenge.map(|c| c.generate());
 
-impl SpamPingJob {
-    pub fn new(ctx: DzContext, http: Arc<Http>, user_id: UserId) -> Self {
-        let (to_task, mut from_ctl) = watch::channel(SpamPingSignal::Stop);
-        let (to_ctl, from_task) = watch::channel(SpamPingStatus::Stopped);
-        
+        let own_ctl = from_ctl.clone();
+        let ping_title = title.clone();
+        let prompt = challenge.as_ref().map(Challenge::prompt);
         tokio::spawn(async move {
-            let channel = 
-                ctx.read().await.get_dm_channel(http.clone(), user_id).await
-                .unwrap();
-            let ping = CreateMessage::new()
-                .content(format!("{} hey buddy", user_id.mention()));
-            'outer: loop {
-                if let Err(e) = from_ctl.changed().await {
-                    // channel closes here
-                    eprintln!("gg: {e}");
+            let outbox = ctx.read().await.outbox.clone();
+            let channel = match ctx.read().await.get_dm_channel(http.clone(), user_id).await {
+                Ok(channel) => channel,
+                Err(e) => {
+                    eprintln!("Failed to get dm channel to nag {user_id}: {e}");
                     return;
                 }
-                'inner: loop {
-                    let val = *from_ctl.borrow_and_update();
-                    match val {
-                        SpamPingSignal::Start => {
-                            to_ctl.send(SpamPingStatus::Active);
-                            // ghost ping user
-                            let msg = channel.send_message(http.clone(), ping.clone())
-                                .await.expect("Failed to send message to user");
-                            let _ = msg.delete(http.clone()).await;
-
-                            tokio::select! {
-                                _ = time::sleep(Duration::from_secs_f32(1.5)) => 
-                                    continue 'inner,
-                                _ = from_ctl.changed() => continue 'inner
-                            };
-                        },
-                        SpamPingSignal::Stop => {
-                            to_ctl.send(SpamPingStatus::Stopped);
-                            break 'inner
-                        },
-                        SpamPingSignal::End => {
-                            to_ctl.send(SpamPingStatus::Stopped);
-                            break 'outer
-                        },
-                    }
+            };
+
+            // ghost pings disappear, so the challenge gets a message that stays
+            if let Some(prompt) = &prompt {
+                let content = format!("**{ping_title}**: {prompt}");
+                if let Err(e) = outbox.send(channel, CreateMessage::new().content(content), Lane::First).await {
+                    eprintln!("Failed to send challenge to {user_id}: {e}");
                 }
-                println!("stop.");
+            }
+
+            // wall clock, the nag may have started before a restart
+            let elapsed = || to_std(OffsetDateTime::now_utc() - started);
+            let mut interval: Option<Duration> = None;
+            let mut pings = 0;
+            // the message counter mode keeps editing
+            let mut counter: Option<Message> = None;
+            loop {
+                // read every time, so changes apply to a running nag
+                let cadence = cadence_rx.borrow().clone();
+                let give_up_after = to_std(cadence.give_up_after);
+                if elapsed() >= give_up_after {
+                    give_up(&ctx, &outbox, channel, occurrence_id, user_id, &ping_title, cadence.give_up_after).await;
+                    break;
+                }
+
+                let mut content = format!("{} {}", user_id.mention(), cadence.line(pings, &ping_title));
+                if let Some(prompt) = &prompt {
+                    content.push_str(&format!(", {prompt}"));
+                }
+                pings += 1;
+                let sent = match (cadence.mode, &mut counter) {
+                    (PingMode::Counter, Some(msg)) => outbox
+                        .edit(channel, msg.id, EditMessage::new().content(format!("{content} (×{pings})")), Lane::Nag)
+                        .await
+                        .map(|_| ()),
+                    (PingMode::Counter, None) => outbox
+                        .send(channel, CreateMessage::new().content(format!("{content} (×{pings})")), Lane::Nag)
+                        .await
+                        .map(|msg| counter = Some(msg)),
+                    (PingMode::Ghost, _) => match outbox.send(channel, CreateMessage::new().content(content), Lane::Nag).await {
+                        // the ping is already out, a full nag queue mustn't leave it behind
+                        Ok(msg) => outbox.delete(channel, msg.id, Lane::First).await,
+                        Err(e) => Err(e)
+                    },
+                    (PingMode::Persistent, _) => outbox
+                        .send(channel, CreateMessage::new().content(content), Lane::Nag)
+                        .await
+                        .map(|_| ())
+                };
+                if let Err(e) = sent {
+                    eprintln!("Failed to nag {user_id}: {e}");
+                }
+
+                // start slow and speed up every ping
+                let fastest = to_std(cadence.interval);
+                let next = interval
+                    .map_or(to_std(cadence.start_interval), |i| i / ESCALATION_DIVISOR)
+                    .max(fastest);
+                interval = Some(next);
+                let left = give_up_after.saturating_sub(elapsed());
+                tokio::select! {
+                    _ = time::sleep(next.min(left)) => {},
+                    // the job was dropped
+                    _ = from_ctl.changed() => return
+                };
+            }
+
+            // the occurrence may have a new nag already, don't remove that one
+            let mut ctx = ctx.write().await;
+            if ctx.nags_ctl.get(&occurrence_id).is_some_and(|job| job.to_task.subscribe().same_channel(&own_ctl)) {
+                ctx.nags_ctl.remove(&occurrence_id);
             }
         });
 
-        SpamPingJob {
+        NagJob {
+            user_id,
+            task_id,
+            title,
+            challenge,
             to_task,
-            from_task
+            cadence: cadence_tx
         }
     }
 
-    pub fn status(&self) -> SpamPingStatus {
-        *self.from_task.borrow()
+    /// Takes effect from the next ping.
+    pub fn set_cadence(&self, cadence: NagCadence) {
+        self.cadence.send_replace(cadence);
     }
+}
 
-    pub fn signal(&self, signal: SpamPingSignal) {
-        self.to_task.send(signal).unwrap();
+/// Starts nagging about an occurrence with the user's current settings.
+/// `started` is in the past when picking a nag back up after a restart, it still gives up on time.
+/// Replaces the nags of older occurrences of the same task, those count as missed by now.
+pub async fn start_nag(
+    ctx: &DzContext, 
+    http: Arc<Http>, 
+    occurrence_id: i64, 
+    task_id: i64, 
+    task_info: &TaskRemindInfo,
+    started: OffsetDateTime
+) -> Result<()> {
+    let db = ctx.read().await.db.clone();
+    let cadence = db.settings(&task_info.user_id).await?.nag;
+    db.set_nag_started(occurrence_id, Some(started)).await?;
+
+    // saved above, it starts over on the next start
+    if ctx.read().await.shutting_down {
+        return Ok(());
     }
+
+    let job = NagJob::new(ctx.clone(), http, occurrence_id, task_id, task_info, cadence, started);
+    let mut ctx = ctx.write().await;
+    ctx.nags_ctl.retain(|_, nag| nag.task_id != task_id);
+    ctx.nags_ctl.insert(occurrence_id, job);
+    Ok(())
+}
+
+/// Tells the buddies about the occurrences that are still pending once their time is up,
+/// all of them or just the one with `occurrence_id`.
+pub async fn watch_for_buddies(ctx: &DzContext, http: Arc<Http>, occurrence_id: Option<i64>) -> Result<()> {
+    let db = ctx.read().await.db.clone();
+    for check in db.buddy_checks(occurrence_id).await? {
+        let (task_ctx, http) = (ctx.clone(), http.clone());
+        ctx.write().await.spawn_background(async move {
+            if let Some(sleep) = sleep_until(check.tell_at) {
+                sleep.await;
+            }
+            if let Err(e) = tell_buddy(&task_ctx, http, &check).await {
+                eprintln!("Failed to tell buddy about occurrence {}: {e}", check.occurrence_id);
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn tell_buddy(ctx: &DzContext, http: Arc<Http>, check: &BuddyCheck) -> Result<()> {
+    let db = ctx.read().await.db.clone();
+    // acknowledged in the meantime, or another watch got to it first
+    if !db.mark_buddy_told(check.occurrence_id).await? {
+        return Ok(());
+    }
+
+    let content = format!(
+        "heads up, {} hasn't checked off **{}** yet, it was due <t:{}:R>. maybe give them a nudge?",
+        check.user_id.mention(),
+        check.title,
+        check.due_at.unix_timestamp()
+    );
+    let channel = ctx.read().await.get_dm_channel(http, check.buddy_id).await?;
+    let outbox = ctx.read().await.outbox.clone();
+    outbox.send(channel, CreateMessage::new().content(content), Lane::First).await?;
+    Ok(())
+}
+
+/// How much faster each nag ping gets than the one before.
+const ESCALATION_DIVISOR: u32 = 2;
+
+fn to_std(dur: ::time::Duration) -> Duration {
+    Duration::try_from(dur).unwrap_or_default()
+}
+
+/// Counts the occurrence as missed and lets the user know.
+async fn give_up(
+    ctx: &DzContext, 
+    outbox: &Outbox, 
+    channel: ChannelId, 
+    occurrence_id: i64, 
+    user_id: UserId, 
+    title: &str, 
+    after: ::time::Duration
+) {
+    let db = ctx.read().await.db.clone();
+    if let Err(e) = db.miss_occurrence(occurrence_id).await {
+        eprintln!("Failed to mark occurrence {occurrence_id} missed: {e}");
+    }
+
+    let content = format!(
+        "{} stopped nagging about **{title}** after **{}**, marked it as missed", 
+        user_id.mention(), 
+        format_duration(after)
+    );
+    if let Err(e) = outbox.send(channel, CreateMessage::new().content(content), Lane::First).await {
+        eprintln!("Failed to send nag summary to {user_id}: {e}");
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ReminderSignal {
+    Run,
+    /// Stops the job and archives the task as cancelled
+    Kill,
+    /// Stops the job but keeps the task around
+    Pause,
 }
 
 pub struct EmbedReminderJob {
-    // would use a oneshot but might add more states later
-    to_task: watch::Sender<bool>,
+    to_task: watch::Sender<ReminderSignal>,
     from_task: Mutex<watch::Receiver<bool>>,
 }
 
 impl EmbedReminderJob {
     pub fn new(ctx: DzContext, http: Arc<Http>, task: &Task) -> Self {
-        let (to_task, mut from_ctl) = watch::channel(false);
+        let (to_task, mut from_ctl) = watch::channel(ReminderSignal::Run);
         let (_to_ctl, from_task) = watch::channel(false);
 
         tokio::spawn(embed_reminder_job(ctx, http, task.clone(), from_ctl));
@@ -108,9 +264,20 @@ impl EmbedReminderJob {
         }
     }
 
+    /// Whether this is the job listening on `from_ctl`.
+    fn controls(&self, from_ctl: &watch::Receiver<ReminderSignal>) -> bool {
+        self.to_task.subscribe().same_channel(from_ctl)
+    }
+
     /// Immediately stops the scheduled reminder task
     pub fn kill(&self) -> Result<()> {
-        self.to_task.send(true)
+        self.to_task.send(ReminderSignal::Kill)
+            .map_err(anyhow::Error::from)
+    }
+
+    /// Immediately stops the scheduled reminder task without deleting it
+    pub fn pause(&self) -> Result<()> {
+        self.to_task.send(ReminderSignal::Pause)
             .map_err(anyhow::Error::from)
     }
 }
@@ -119,47 +286,117 @@ async fn embed_reminder_job(
     ctx: DzContext,
     http: Arc<Http>,
     task: Task,
-    mut from_ctl: watch::Receiver<bool>,
+    mut from_ctl: watch::Receiver<ReminderSignal>,
 ) {
     let task_info = task.remind_info();
     let id = task.id();
 
-    let remove = || async {
-        let mut ctx = ctx.write().await;
-        ctx.reminders_ctl.remove_entry(&id);
-        ctx.db.delete_task(id).await.expect("Could not delte for some reason");
+    // the task may have been rescheduled already, don't remove the new job
+    let own_ctl = from_ctl.clone();
+    let stop = |archive: Option<ArchiveReason>| {
+        let ctx = ctx.clone();
+        let own_ctl = own_ctl.clone();
+        async move {
+            let mut ctx = ctx.write().await;
+            if ctx.reminders_ctl.get(&id).is_some_and(|job| job.controls(&own_ctl)) {
+                ctx.reminders_ctl.remove_entry(&id);
+            }
+            if let Some(reason) = archive {
+                if let Err(e) = ctx.db.archive_task(id, reason).await {
+                    eprintln!("Failed to archive task {id}: {e}");
+                }
+            }
+        }
     };
+    let on_signal = |signal: ReminderSignal| 
+        (signal == ReminderSignal::Kill).then_some(ArchiveReason::Cancelled);
 
     loop {
-        if let Some(sleep) = sleep_until_next(&task) {
-            tokio::select! {
-                _ = sleep => {
-                    // do nothing and continue
-                },
-                _ = from_ctl.changed() => {
-                    // this means a cancel signal has been sent.
-                    remove().await;
-                    return;
-                }
-            };
-        } else {
+        let Some(due_at) = next_occurrence_time(&task) else {
             // theres no more times to repeat this task
-            // remove remind task
-            remove().await;
+            // archive remind task, unless it's chained and can run again
+            let archive = match task.chained_after() {
+                Some((after, _)) if waits_for(&ctx, after).await => None,
+                _ => Some(finished_reason(&ctx, id).await)
+            };
+            stop(archive).await;
             // kill this thread
             return;
+        };
+        println!("next occurence time: {due_at}");
+
+        // heads-up reminders go out before the main one, longest lead first.
+        for lead in task.lead_times() {
+            let Some(sleep) = sleep_until(due_at - lead) else {
+                // too late for this one
+                continue;
+            };
+            if let Some(signal) = wait_or_signal(sleep, &mut from_ctl).await {
+                stop(on_signal(signal)).await;
+                return;
+            }
+            if let Err(e) = send_heads_up(http.clone(), ctx.clone(), task_info.clone(), due_at).await {
+                eprintln!("Failed to send heads-up for task {id}: {e}");
+            }
+        }
+
+        let sleep = sleep_until(due_at)
+            .unwrap_or_else(|| time::sleep(Duration::ZERO));
+        if let Some(signal) = wait_or_signal(sleep, &mut from_ctl).await {
+            // this means a kill or pause signal has been sent.
+            stop(on_signal(signal)).await;
+            return;
+        }
+
+        let (occurrence, footer) = match record_occurrence(&ctx, &task, due_at).await {
+            Ok((occurrence, footer)) => (Some(occurrence), footer),
+            Err(e) => {
+                eprintln!("Failed to record occurrence of task {id}: {e}");
+                (None, None)
+            }
+        };
+
+        if let Err(e) = deliver(http.clone(), ctx.clone(), id, task_info.clone(), occurrence.map(|o| o.id), footer).await {
+            eprintln!("Failed to send reminder for task {id}: {e}");
+        }
+    }
+}
+
+/// Sends the reminder with its files and starts nagging if the task asks for it.
+/// Also used to send it again once a snooze is over.
+pub async fn deliver(
+    http: Arc<Http>,
+    ctx: DzContext,
+    task_id: i64,
+    task_info: TaskRemindInfo,
+    occurrence_id: Option<i64>,
+    footer: Option<String>,
+) -> Result<()> {
+    let files = match ctx.read().await.db.attachments_for(task_id).await {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Failed to get attachments of task {task_id}: {e}");
+            vec![]
         }
+    };
 
-        send_embed(
-            http.clone(), 
-            ctx.clone(),
-            task_info.clone(), 
-        ).await.unwrap();
+    send_embed(
+        http.clone(), 
+        ctx.clone(),
+        task_info.clone(), 
+        occurrence_id,
+        footer,
+        files,
+    ).await?;
 
-        let m = ctx.read().await;
-        let ctl = m.spammer_ctl.get(&task_info.user_id).unwrap();
-        ctl.signal(SpamPingSignal::Start);
+    if let (Priority::Nag, Some(occurrence_id)) = (task_info.priority, occurrence_id) {
+        start_nag(&ctx, http.clone(), occurrence_id, task_id, &task_info, OffsetDateTime::now_utc()).await?;
+    }
+    if let Some(occurrence_id) = occurrence_id {
+        watch_for_buddies(&ctx, http, Some(occurrence_id)).await?;
     }
+
+    Ok(())
 }
 
 /// Returns the next occurence or None if there isnt one.
@@ -170,11 +407,22 @@ pub fn next_occurrence_time(task: &Task) -> Option<OffsetDateTime> {
             let dt = date.with_time(*remind_at).assume_utc();
             (dt > now).then_some(dt)
         }
+        Task::Deadline { remind_at, date, offsets, .. } => {
+            let deadline = date.with_time(*remind_at).assume_utc();
+            offsets.iter()
+                .map(|o| deadline - *o)
+                .chain(std::iter::once(deadline))
+                .filter(|dt| *dt > now)
+                .min()
+        }
+        Task::Chained { fire_at, .. } => fire_at.filter(|dt| *dt > now),
         Task::Recurring { remind_at, on_days, repeat_weekly, created_at, .. } => {
             // use the previous day as the referece point for date.next_occurence(Weekday), because the current day can count as well.
-            let ref_date = created_at.date().previous_day().unwrap();
+            let ref_date = created_at.date().saturating_sub(::time::Duration::DAY);
 
             if *repeat_weekly {
+                // from yesterday once that's later, or it would run out two weeks after creation
+                let ref_date = ref_date.max(now.date().saturating_sub(::time::Duration::DAY));
                 let closest = on_days.iter()
                     .map(|d| {
                         let dt = ref_date.next_occurrence(*d).with_time(*remind_at).assume_utc();
@@ -200,16 +448,99 @@ pub fn next_occurrence_time(task: &Task) -> Option<OffsetDateTime> {
     }
 }
 
-/// Returns None if there is no next occurrence
-fn sleep_until_next(task: &Task) -> Option<Sleep> {
-    let next = next_occurrence_time(task)?;
-    println!("next occurence time: {next}");
+/// Every time the task was due in `(from, to]`, oldest first.
+/// Mirrors [`next_occurrence_time`], for finding the reminders the bot was down for.
+pub fn due_times_between(task: &Task, from: OffsetDateTime, to: OffsetDateTime) -> Vec<OffsetDateTime> {
+    let within = |dt: &OffsetDateTime| *dt > from && *dt <= to;
+    match task {
+        Task::Once { remind_at, date, .. } => {
+            Some(date.with_time(*remind_at).assume_utc())
+                .filter(within)
+                .into_iter()
+                .collect()
+        }
+        Task::Deadline { remind_at, date, offsets, .. } => {
+            let deadline = date.with_time(*remind_at).assume_utc();
+            offsets.iter()
+                .map(|o| deadline - *o)
+                .chain(std::iter::once(deadline))
+                .filter(within)
+                .sorted()
+                .collect()
+        }
+        Task::Chained { fire_at, .. } => fire_at.filter(within).into_iter().collect(),
+        Task::Recurring { remind_at, on_days, repeat_weekly, created_at, .. } => {
+            let mut times = vec![];
+            let mut date = from.date();
+            while date <= to.date() {
+                let dt = date.with_time(*remind_at).assume_utc();
+                // without repeating, only the first week counts
+                let in_range = *repeat_weekly || (dt - *created_at).whole_days() < 7;
+                if on_days.contains(&date.weekday()) && within(&dt) && dt > *created_at && in_range {
+                    times.push(dt);
+                }
+                let Some(next) = date.next_day() else { break };
+                date = next;
+            }
+            times
+        }
+    }
+}
+
+/// Completed if the last occurrence was done. It may still be acknowledged
+/// later, see [`Database::complete_occurrence`].
+async fn finished_reason(ctx: &DzContext, id: i64) -> ArchiveReason {
+    let db = ctx.read().await.db.clone();
+    match db.occurrences_for(id).await {
+        Ok(history) if history.last().is_some_and(|o| o.status == OccurrenceStatus::Done) => 
+            ArchiveReason::Completed,
+        Ok(_) => ArchiveReason::Expired,
+        Err(e) => {
+            eprintln!("Failed to get occurrences of task {id}: {e}");
+            ArchiveReason::Expired
+        }
+    }
+}
+
+/// Whether a task chained after `after` could still be set off by it.
+pub async fn waits_for(ctx: &DzContext, after: i64) -> bool {
+    let db = ctx.read().await.db.clone();
+    db.can_set_off_chains(after).await.unwrap_or_else(|e| {
+        eprintln!("Failed to check task {after}: {e}");
+        true
+    })
+}
+
+/// Saves the occurrence to the database.
+/// Also returns the habit stats footer if the task is recurring.
+async fn record_occurrence(
+    ctx: &DzContext, 
+    task: &Task, 
+    due_at: OffsetDateTime
+) -> Result<(Occurrence, Option<String>)> {
+    let db = ctx.read().await.db.clone();
+    let checklist_len = checklist::items(&task.remind_info().info).len() as i32;
+    let occurrence = db.add_occurrence(task.id(), task.user_id(), due_at, checklist_len).await?;
+
+    if !task.recurring() {
+        return Ok((occurrence, None));
+    }
+
+    let history = db.occurrences_for(task.id()).await?;
+    let footer = HabitStats::compute(&history, OffsetDateTime::now_utc()).footer();
+    Ok((occurrence, Some(footer)))
+}
+
+/// Returns None if the time has already passed.
+fn sleep_until(dt: OffsetDateTime) -> Option<Sleep> {
     let instant = Instant::now();
     let now = OffsetDateTime::now_utc();
-    
-    assert!(next >= now, "gg cooked");
 
-    let dur = next - now;
+    if dt < now {
+        return None;
+    }
+
+    let dur = dt - now;
     let dur = std::time::Duration::new(
         dur.whole_seconds() as u64,
         dur.subsec_nanoseconds() as u32
@@ -218,21 +549,211 @@ fn sleep_until_next(task: &Task) -> Option<Sleep> {
     Some(time::sleep_until(instant + dur))
 }
 
+/// Returns the signal if one was sent before the sleep finished.
+async fn wait_or_signal(sleep: Sleep, from_ctl: &mut watch::Receiver<ReminderSignal>) -> Option<ReminderSignal> {
+    tokio::select! {
+        _ = sleep => None,
+        _ = from_ctl.changed() => Some(*from_ctl.borrow_and_update())
+    }
+}
+
 async fn send_embed(
     http: Arc<Http>,
     ctx: DzContext, 
     task_info: TaskRemindInfo, 
+    occurrence_id: Option<i64>,
+    footer: Option<String>,
+    files: Vec<Attachment>,
+) -> Result<()> {
+    let mut message = attachments::attach(
+        CreateMessage::new(), 
+        reminder_embed(&task_info, &[], footer, None), 
+        files
+    );
+
+    message = match task_info.priority {
+        Priority::Silent => message.flags(MessageFlags::SUPPRESS_NOTIFICATIONS),
+        // the nag takes care of it
+        Priority::Nag if occurrence_id.is_some() => message,
+        // no occurrence to acknowledge means no nag, ping once instead
+        Priority::Ping | Priority::Nag => message.content(task_info.user_id.mention().to_string()),
+    };
+
+    if let Some(occurrence_id) = occurrence_id {
+        message = message.components(reminder_components(occurrence_id, &task_info.info, &[], false));
+    }
+
+    let channel = ctx.read().await.get_dm_channel(http.clone(), task_info.user_id)
+        .await?;
+    let outbox = ctx.read().await.outbox.clone();
+
+    outbox.send(channel, message, Lane::First)
+        .await?;
+
+    Ok(())
+}
+
+/// `checked` is the ticked checklist items, if the info has any.
+/// `outcome` says what the user did with the reminder, once they did something.
+pub fn reminder_embed(
+    task_info: &TaskRemindInfo, 
+    checked: &[i32], 
+    footer: Option<String>, 
+    outcome: Option<String>
+) -> CreateEmbed {
+    let mut description = checklist::render(&task_info.info, checked);
+    if let Some(deadline) = task_info.deadline {
+        description = format!("{}\n\n{description}", time_left(deadline));
+    }
+    if let Some(outcome) = outcome {
+        description = format!("{outcome}\n\n{description}");
+    }
+
+    let mut embed = task_info.style.apply(
+        CreateEmbed::new()
+            .title(&task_info.title)
+            .description(description)
+    );
+
+    if let Some(footer) = footer {
+        embed = embed.footer(CreateEmbedFooter::new(footer));
+    }
+
+    embed
+}
+
+/// The checklist buttons and a row of done/snooze/skip under them.
+pub fn reminder_components(occurrence_id: i64, info: &str, checked: &[i32], disabled: bool) -> Vec<CreateActionRow> {
+    let action = |action: OccurrenceAction, label: &str, style: ButtonStyle| {
+        CreateButton::new(ComponentId::Resolve { occurrence_id, action }.encode())
+            .label(label)
+            .style(style)
+            .disabled(disabled)
+    };
+
+    let mut rows = checklist::buttons(occurrence_id, info, checked, disabled);
+    rows.push(CreateActionRow::Buttons(vec![
+        action(OccurrenceAction::Done, "Done", ButtonStyle::Success),
+        action(OccurrenceAction::Snooze, &format!("Snooze {}", format_duration(SNOOZE)), ButtonStyle::Secondary),
+        action(OccurrenceAction::Skip, "Skip", ButtonStyle::Secondary),
+    ]));
+    rows
+}
+
+fn time_left(deadline: OffsetDateTime) -> String {
+    let left = deadline - OffsetDateTime::now_utc();
+    // round to the minute, the reminder is never sent exactly on time
+    let left = ::time::Duration::minutes((left.whole_seconds() + 30) / 60);
+    if left.is_positive() {
+        format!("⏰ **{}** left, due <t:{}:f>", format_duration(left), deadline.unix_timestamp())
+    } else {
+        "⏰ **due now**".into()
+    }
+}
+
+/// A quiet embed sent ahead of the main reminder. Never nags.
+async fn send_heads_up(
+    http: Arc<Http>,
+    ctx: DzContext, 
+    task_info: TaskRemindInfo, 
+    due_at: OffsetDateTime,
 ) -> Result<()> {
     let embed = CreateEmbed::new()
-        .title(task_info.title)
-        .description(task_info.info)
-        .color(Colour::from_rgb(255, 255, 255));
+        .title(format!("Heads up: {}", task_info.title))
+        .description(format!("coming up <t:{}:R>", due_at.unix_timestamp()))
+        .color(Colour::from_rgb(150, 150, 150));
 
     let channel = ctx.read().await.get_dm_channel(http.clone(), task_info.user_id)
         .await?;
+    let outbox = ctx.read().await.outbox.clone();
 
-    channel.send_message(http.clone(), CreateMessage::new().embed(embed))
+    outbox.send(channel, CreateMessage::new().embed(embed), Lane::First)
         .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use ::time::{Duration, Time};
+
+    use super::*;
+    use crate::embed_style::EmbedStyle;
+
+    const EVERY_DAY: [Weekday; 7] = [
+        Weekday::Monday, Weekday::Tuesday, Weekday::Wednesday, Weekday::Thursday,
+        Weekday::Friday, Weekday::Saturday, Weekday::Sunday
+    ];
+
+    fn recurring(created_at: OffsetDateTime, on_days: &[Weekday], repeat_weekly: bool) -> Task {
+        Task::Recurring {
+            id: 1,
+            user_id: UserId::new(1),
+            title: "stretch".into(),
+            info: String::new(),
+            remind_at: Time::from_hms(12, 0, 0).unwrap(),
+            on_days: HashSet::from_iter(on_days.iter().copied()),
+            repeat_weekly,
+            lead_times: vec![],
+            tags: vec![],
+            paused: false,
+            priority: Priority::default(),
+            challenge: None,
+            style: EmbedStyle::default(),
+            created_at
+        }
+    }
+
+    fn deadline(at: OffsetDateTime, offsets: Vec<Duration>) -> Task {
+        Task::Deadline {
+            id: 1,
+            user_id: UserId::new(1),
+            title: "taxes".into(),
+            info: String::new(),
+            remind_at: at.time(),
+            date: at.date(),
+            offsets,
+            lead_times: vec![],
+            tags: vec![],
+            paused: false,
+            priority: Priority::default(),
+            challenge: None,
+            style: EmbedStyle::default()
+        }
+    }
+
+    #[test]
+    fn weekly_repeats_keep_going_after_two_weeks() {
+        let now = OffsetDateTime::now_utc();
+        for weeks_ago in [0, 2, 3, 10] {
+            for day in EVERY_DAY {
+                let task = recurring(now - Duration::weeks(weeks_ago), &[day], true);
+                let next = next_occurrence_time(&task).unwrap();
+                assert!(next > now, "{day} created {weeks_ago} weeks ago is due in the past");
+                assert!(next - now <= Duration::weeks(1), "{day} created {weeks_ago} weeks ago skips a week");
+                assert_eq!(next.weekday(), day);
+            }
+        }
+    }
+
+    #[test]
+    fn one_week_tasks_run_out() {
+        let now = OffsetDateTime::now_utc();
+        assert!(next_occurrence_time(&recurring(now, &EVERY_DAY, false)).is_some());
+        assert!(next_occurrence_time(&recurring(now - Duration::weeks(2), &EVERY_DAY, false)).is_none());
+    }
+
+    #[test]
+    fn deadlines_go_through_their_offsets() {
+        let now = OffsetDateTime::now_utc();
+        let offsets = vec![Duration::days(7), Duration::days(1)];
+
+        let due = now + Duration::days(10);
+        assert_eq!(next_occurrence_time(&deadline(due, offsets.clone())), Some(due - Duration::days(7)));
+        let due = now + Duration::days(3);
+        assert_eq!(next_occurrence_time(&deadline(due, offsets.clone())), Some(due - Duration::days(1)));
+        let due = now + Duration::hours(3);
+        assert_eq!(next_occurrence_time(&deadline(due, offsets.clone())), Some(due));
+        assert_eq!(next_occurrence_time(&deadline(now - Duration::hours(1), offsets)), None);
+    }
+}
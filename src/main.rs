@@ -5,10 +5,21 @@ mod scheduler;
 mod jobs;
 mod database;
 mod time_parse;
+mod stats;
+mod commands;
+mod components;
+mod checklist;
+mod attachments;
+mod embed_style;
+mod ack;
+mod challenge;
+mod outbox;
+mod catch_up;
 use bot::DZBot;
 use database::Database;
 use serenity::prelude::*;
 use anyhow::Result;
+use tokio::signal;
 
 
 #[tokio::main]
@@ -32,15 +43,45 @@ async fn main() -> Result<()> {
         pg_port.parse().expect("Port was not an unsigned integer.")
     ).await?;
 
+    let bot = DZBot::new(Arc::new(db));
+    let ctx = bot.ctx.clone();
     let mut client =
     Client::builder(&token, intents).event_handler(
-        DZBot::new(Arc::new(db))
+        bot
     ).await.expect("Err creating client");
 
+    let shard_manager = client.shard_manager.clone();
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        println!("Shutting down...");
+        bot::shut_down(&ctx).await;
+        // this makes client.start() return
+        shard_manager.shutdown_all().await;
+    });
+
     println!("Starting bot...");
     if let Err(why) = client.start().await {
         println!("Client error: {why:?}");
     }
 
+    println!("Bye");
     Ok(())
+}
+
+/// Ctrl-C, or SIGTERM from whatever is running the bot.
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Couldn't listen for SIGTERM");
+        tokio::select! {
+            _ = signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    if let Err(e) = signal::ctrl_c().await {
+        eprintln!("Couldn't listen for ctrl-c: {e}");
+        std::future::pending::<()>().await;
+    }
 }
\ No newline at end of file
@@ -1,17 +1,27 @@
 use std::{collections::HashSet, convert::{TryFrom, TryInto}, f32::consts::PI};
 use std::iter::FromIterator;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
 use chrono::{Local, Offset};
 use serenity::all::UserId;
 use sqlx::{postgres::PgHasArrayType, types::time::{Date, OffsetDateTime}};
-use time::{convert::Week, Time, Weekday};
+use time::{convert::Week, Duration, Time, Weekday};
 use ::time::UtcOffset;
 
+use crate::challenge::ChallengeKind;
+use crate::embed_style::EmbedStyle;
+use crate::time_parse::format_duration;
+
 /// Database row structs
 #[derive(sqlx::FromRow)]
 pub struct UserSettingsRow {
-    pub user_id: String,
-    pub ack_phrase: String
+    pub ack_phrase: String,
+    pub deadline_offset_secs: Vec<i32>,
+    pub nag_interval_ms: i32,
+    pub nag_start_interval_ms: i32,
+    pub nag_give_up_secs: i32,
+    pub ping_mode: String,
+    pub nag_lines: Vec<String>
 }
 
 #[derive(sqlx::FromRow)]
@@ -24,21 +34,193 @@ pub struct TaskRow {
     pub on_days: Option<Vec<i32>>, 
     pub repeat_weekly: bool,
     pub time_created: OffsetDateTime,
-    pub on_date: Option<Date>
+    pub on_date: Option<Date>,
+    pub lead_secs: Vec<i32>,
+    pub tags: Vec<String>,
+    pub paused: bool,
+    pub priority: String,
+    pub kind: String,
+    pub offset_secs: Vec<i32>,
+    pub archived_at: Option<OffsetDateTime>,
+    pub archive_reason: Option<String>,
+    pub colour: Option<i32>,
+    pub url: Option<String>,
+    pub thumbnail: Option<String>,
+    pub field_names: Vec<String>,
+    pub field_values: Vec<String>,
+    pub after_task: Option<i64>,
+    pub delay_secs: Option<i32>,
+    pub fire_at: Option<OffsetDateTime>,
+    pub challenge: Option<String>
+}
+
+#[derive(sqlx::FromRow)]
+pub struct OccurrenceRow {
+    pub id: i64,
+    pub task_id: i64,
+    pub due_at: OffsetDateTime,
+    pub status: String,
+    pub checklist_len: i32,
+    pub snoozed_until: Option<OffsetDateTime>,
+    pub nag_started_at: Option<OffsetDateTime>
+}
+
+#[derive(sqlx::FromRow)]
+pub struct BuddyRow {
+    pub id: i64,
+    pub user_id: String,
+    pub buddy_id: String,
+    pub task_id: Option<i64>,
+    pub after_secs: i32,
+    pub status: String
+}
+
+#[derive(sqlx::FromRow)]
+pub struct TemplateRow {
+    pub name: String,
+    pub title: String,
+    pub info: String,
+    pub time_line: String
+}
+
+#[derive(sqlx::FromRow)]
+pub struct AttachmentRow {
+    pub filename: String,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>
+}
+
+/// Durations are stored as whole seconds in INT columns, longer ones are turned away.
+pub fn duration_to_secs(duration: Duration) -> Result<i32> {
+    i32::try_from(duration.whole_seconds())
+        .map_err(|_| anyhow!("{} is too long, it can be at most {} days", format_duration(duration), i32::MAX / 86400))
+}
+
+pub fn durations_to_secs(durations: &[Duration]) -> Result<Vec<i32>> {
+    durations.iter().copied().map(duration_to_secs).collect()
+}
+
+/// Nag intervals are stored in milliseconds, which runs out a lot sooner.
+pub fn duration_to_millis(duration: Duration) -> Result<i32> {
+    i32::try_from(duration.whole_milliseconds())
+        .map_err(|_| anyhow!("{} is too long, it can be at most {} days", format_duration(duration), i32::MAX / 86_400_000))
+}
+
+pub fn secs_to_duration(secs: i32) -> Duration {
+    Duration::seconds(secs as i64)
+}
+
+pub fn secs_to_durations(secs: &[i32]) -> Vec<Duration> {
+    secs.iter().copied().map(secs_to_duration).collect()
 }
 
 /// Returned structs
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 
 pub struct UserSettings {
-    pub ack_phrase: String
+    pub ack_phrase: String,
+    /// How long before a deadline to remind, copied onto new deadline tasks
+    pub deadline_offsets: Vec<Duration>,
+    pub nag: NagCadence
+}
+
+/// How the bot pings until a reminder is acknowledged.
+/// It starts at `start_interval` and speeds up to `interval`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NagCadence {
+    pub start_interval: Duration,
+    /// The fastest it gets
+    pub interval: Duration,
+    /// Stop and count the reminders as missed after this long
+    pub give_up_after: Duration,
+    /// Pings go through these in turn, never empty
+    pub lines: Vec<String>,
+    pub mode: PingMode
+}
+
+impl Default for NagCadence {
+    fn default() -> Self {
+        Self {
+            start_interval: Duration::minutes(1),
+            interval: Duration::milliseconds(1500),
+            give_up_after: Duration::minutes(30),
+            lines: vec!["hey buddy".into()],
+            mode: PingMode::Ghost
+        }
+    }
+}
+
+impl NagCadence {
+    /// Stands for the reminder's title in nag lines.
+    pub const TITLE_PLACEHOLDER: &'static str = "{title}";
+
+    /// The line for the `n`th ping. Lines without the placeholder get the title after them.
+    pub fn line(&self, n: usize, title: &str) -> String {
+        let line = self.lines.get(n % self.lines.len().max(1)).map_or("", String::as_str);
+        if line.contains(Self::TITLE_PLACEHOLDER) {
+            line.replace(Self::TITLE_PLACEHOLDER, &format!("**{title}**"))
+        } else {
+            format!("{line} - **{title}**")
+        }
+    }
+}
+
+/// What happens to the nag pings after they're sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingMode {
+    /// Deleted right away, only the notification is left
+    Ghost,
+    /// Left in the chat
+    Persistent,
+    /// A single message, edited with the number of pings so far
+    Counter
+}
+
+impl PingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ghost => "ghost",
+            Self::Persistent => "persistent",
+            Self::Counter => "counter"
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(
+            match s {
+                "ghost" => Self::Ghost,
+                "persistent" => Self::Persistent,
+                "counter" => Self::Counter,
+                _ => anyhow::bail!("Invalid ping mode: {s}")
+            }
+        )
+    }
+}
+
+/// Used for users that haven't changed anything yet
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            ack_phrase: "ok".into(),
+            deadline_offsets: vec![Duration::days(7), Duration::days(1), Duration::hours(3)],
+            nag: NagCadence::default()
+        }
+    }
 }
 
 impl UserSettings {
     pub fn from_row_struct(row: UserSettingsRow) -> Result<Self> {
         Ok(
             Self {
-                ack_phrase: row.ack_phrase
+                ack_phrase: row.ack_phrase,
+                deadline_offsets: secs_to_durations(&row.deadline_offset_secs),
+                nag: NagCadence {
+                    start_interval: Duration::milliseconds(row.nag_start_interval_ms as i64),
+                    interval: Duration::milliseconds(row.nag_interval_ms as i64),
+                    give_up_after: secs_to_duration(row.nag_give_up_secs),
+                    lines: row.nag_lines,
+                    mode: PingMode::parse(&row.ping_mode)?
+                }
             }
         )
     }
@@ -54,6 +236,12 @@ pub enum Task {
         remind_at: Time,
         on_days: HashSet<Weekday>, 
         repeat_weekly: bool,
+        lead_times: Vec<Duration>,
+        tags: Vec<String>,
+        paused: bool,
+        priority: Priority,
+        challenge: Option<ChallengeKind>,
+        style: EmbedStyle,
         created_at: OffsetDateTime
     },
     Once {
@@ -63,7 +251,77 @@ pub enum Task {
         info: String,
         remind_at: Time,
         date: Date,
+        lead_times: Vec<Duration>,
+        tags: Vec<String>,
+        paused: bool,
+        priority: Priority,
+        challenge: Option<ChallengeKind>,
+        style: EmbedStyle,
         created_at: OffsetDateTime
+    },
+    /// Reminds at each offset before the deadline, then at the deadline itself.
+    Deadline {
+        id: i64,
+        user_id: UserId,
+        title: String,
+        info: String,
+        remind_at: Time,
+        date: Date,
+        offsets: Vec<Duration>,
+        lead_times: Vec<Duration>,
+        tags: Vec<String>,
+        paused: bool,
+        priority: Priority,
+        challenge: Option<ChallengeKind>,
+        style: EmbedStyle
+    },
+    /// Runs `delay` after an occurrence of the `after` task is done.
+    Chained {
+        id: i64,
+        user_id: UserId,
+        title: String,
+        info: String,
+        after: i64,
+        delay: Duration,
+        /// None while waiting for the other task
+        fire_at: Option<OffsetDateTime>,
+        lead_times: Vec<Duration>,
+        tags: Vec<String>,
+        paused: bool,
+        priority: Priority,
+        challenge: Option<ChallengeKind>,
+        style: EmbedStyle
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskKind {
+    Once,
+    Recurring,
+    Deadline,
+    Chained
+}
+
+impl TaskKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Once => "once",
+            Self::Recurring => "recurring",
+            Self::Deadline => "deadline",
+            Self::Chained => "chained"
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(
+            match s {
+                "once" => Self::Once,
+                "recurring" => Self::Recurring,
+                "deadline" => Self::Deadline,
+                "chained" => Self::Chained,
+                _ => anyhow::bail!("Invalid task kind: {s}")
+            }
+        )
     }
 }
 
@@ -81,33 +339,88 @@ impl Task {
                 _ => panic!("Invalid weekday number: {}", i),
             }
         };
+        let priority = Priority::parse(&row.priority)?;
+        let challenge = row.challenge.as_deref().map(ChallengeKind::parse).transpose()?;
+        let lead_times = secs_to_durations(&row.lead_secs);
+        let style = EmbedStyle {
+            colour: row.colour.map(|c| c as u32),
+            url: row.url,
+            thumbnail: row.thumbnail,
+            fields: row.field_names.into_iter().zip(row.field_values).collect()
+        };
         Ok(
-            if let Some(date) = row.on_date {
-                Self::Once {
-                    id: row.id,
-                    user_id: UserId::new(row.user_id.parse::<u64>()?),
-                    title: row.title,
-                    info: row.info,
-                    remind_at: row.remind_at,
-                    date,
-                    created_at: row.time_created
-                }
-            } else {
-                Self::Recurring {
-                    id: row.id,
-                    user_id: UserId::new(row.user_id.parse::<u64>()?),
-                    title: row.title,
-                    info: row.info,
-                    remind_at: row.remind_at,
-                    // row.on_days should never be None bc input validation!
-                    on_days: {
-                        HashSet::from_iter(
-                            row.on_days.unwrap().iter().map(weekday_from_i32)
-                        )
+            // on_date and on_days should never be None bc input validation!
+            match TaskKind::parse(&row.kind)? {
+                TaskKind::Once => 
+                    Self::Once {
+                        id: row.id,
+                        user_id: UserId::new(row.user_id.parse::<u64>()?),
+                        title: row.title,
+                        info: row.info,
+                        remind_at: row.remind_at,
+                        date: row.on_date.unwrap(),
+                        lead_times,
+                        tags: row.tags,
+                        paused: row.paused,
+                        priority,
+                        challenge,
+                        style: style.clone(),
+                        created_at: row.time_created
                     },
-                    repeat_weekly: row.repeat_weekly,
-                    created_at: row.time_created
-                }
+                TaskKind::Deadline => 
+                    Self::Deadline {
+                        id: row.id,
+                        user_id: UserId::new(row.user_id.parse::<u64>()?),
+                        title: row.title,
+                        info: row.info,
+                        remind_at: row.remind_at,
+                        date: row.on_date.unwrap(),
+                        offsets: secs_to_durations(&row.offset_secs),
+                        lead_times,
+                        tags: row.tags,
+                        paused: row.paused,
+                        priority,
+                        challenge,
+                        style: style.clone()
+                    },
+                TaskKind::Recurring => 
+                    Self::Recurring {
+                        id: row.id,
+                        user_id: UserId::new(row.user_id.parse::<u64>()?),
+                        title: row.title,
+                        info: row.info,
+                        remind_at: row.remind_at,
+                        on_days: {
+                            HashSet::from_iter(
+                                row.on_days.unwrap().iter().map(weekday_from_i32)
+                            )
+                        },
+                        repeat_weekly: row.repeat_weekly,
+                        lead_times,
+                        tags: row.tags,
+                        paused: row.paused,
+                        priority,
+                        challenge,
+                        style: style.clone(),
+                        created_at: row.time_created
+                    },
+                TaskKind::Chained => 
+                    Self::Chained {
+                        id: row.id,
+                        user_id: UserId::new(row.user_id.parse::<u64>()?),
+                        title: row.title,
+                        info: row.info,
+                        after: row.after_task
+                            .ok_or(anyhow::anyhow!("Chained task {} has no after_task", row.id))?,
+                        delay: secs_to_duration(row.delay_secs.unwrap_or_default()),
+                        fire_at: row.fire_at,
+                        lead_times,
+                        tags: row.tags,
+                        paused: row.paused,
+                        priority,
+                        challenge,
+                        style: style.clone()
+                    }
             }
         )
     }
@@ -115,59 +428,136 @@ impl Task {
     pub fn id(&self) -> i64 {
         match self {
             Self::Recurring { id, .. }
-            | Self::Once { id, .. } => *id
+            | Self::Once { id, .. }
+            | Self::Deadline { id, .. }
+            | Self::Chained { id, .. } => *id
         }
     }
 
     pub fn user_id(&self) -> &UserId {
         match self {
             Self::Recurring { user_id, .. }
-            | Self::Once { user_id, .. } => user_id
+            | Self::Once { user_id, .. }
+            | Self::Deadline { user_id, .. }
+            | Self::Chained { user_id, .. } => user_id
         }
     }
 
     pub fn repeats_weekly(&self) -> bool {
         match self {
             Self::Recurring { repeat_weekly, .. } => *repeat_weekly,
-            Self::Once { .. } => false
+            Self::Once { .. } | Self::Deadline { .. } | Self::Chained { .. } => false
         }
     }
 
     pub fn remind_at(&self) -> Time {
         match self {
             Self::Recurring { remind_at, .. }
-            | Self::Once { remind_at, .. } => *remind_at
+            | Self::Once { remind_at, .. }
+            | Self::Deadline { remind_at, .. } => *remind_at,
+            Self::Chained { fire_at, .. } => fire_at.map(|f| f.time()).unwrap_or(Time::MIDNIGHT)
         }
     }
 
-    pub fn created_at(&self) -> &OffsetDateTime {
+    /// How long before each occurrence to send a heads-up, longest first.
+    pub fn lead_times(&self) -> Vec<Duration> {
+        match self {
+            Self::Recurring { lead_times, .. }
+            | Self::Once { lead_times, .. }
+            | Self::Deadline { lead_times, .. }
+            | Self::Chained { lead_times, .. } => lead_times.iter().copied().sorted().rev().collect()
+        }
+    }
+
+    pub fn tags(&self) -> &[String] {
+        match self {
+            Self::Recurring { tags, .. }
+            | Self::Once { tags, .. }
+            | Self::Deadline { tags, .. }
+            | Self::Chained { tags, .. } => tags
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        match self {
+            Self::Recurring { paused, .. }
+            | Self::Once { paused, .. }
+            | Self::Deadline { paused, .. }
+            | Self::Chained { paused, .. } => *paused
+        }
+    }
+
+    /// How long before the deadline to remind, longest first. Empty unless it's a deadline.
+    pub fn deadline_offsets(&self) -> Vec<Duration> {
+        match self {
+            Self::Deadline { offsets, .. } => offsets.iter().copied().sorted().rev().collect(),
+            Self::Recurring { .. } | Self::Once { .. } | Self::Chained { .. } => vec![]
+        }
+    }
+
+    pub fn kind(&self) -> TaskKind {
+        match self {
+            Self::Recurring { .. } => TaskKind::Recurring,
+            Self::Once { .. } => TaskKind::Once,
+            Self::Deadline { .. } => TaskKind::Deadline,
+            Self::Chained { .. } => TaskKind::Chained
+        }
+    }
+
+    /// None for deadlines and chained tasks, nothing about them goes by when they were made.
+    pub fn created_at(&self) -> Option<&OffsetDateTime> {
         match self {
             Self::Recurring { created_at, .. }
-            | Self::Once { created_at, .. } => created_at
+            | Self::Once { created_at, .. } => Some(created_at),
+            Self::Deadline { .. } | Self::Chained { .. } => None
+        }
+    }
+
+    /// The task this one waits for and how long after it runs, if it's chained.
+    pub fn chained_after(&self) -> Option<(i64, Duration)> {
+        match self {
+            Self::Chained { after, delay, .. } => Some((*after, *delay)),
+            Self::Recurring { .. } | Self::Once { .. } | Self::Deadline { .. } => None
         }
     }
 
     pub fn recurring(&self) -> bool {
         match self {
             Self::Recurring {..} => true,
-            Self::Once {..} => false
+            Self::Once {..} | Self::Deadline {..} | Self::Chained {..} => false
         }
     }
 
     pub fn remind_info(&self) -> TaskRemindInfo {
         match self {
-            Self::Once { user_id, title, info, .. } | Task::Recurring { user_id, title, info, .. } => 
+            Self::Once { user_id, title, info, priority, challenge, style, .. } 
+            | Task::Recurring { user_id, title, info, priority, challenge, style, .. } 
+            | Task::Chained { user_id, title, info, priority, challenge, style, .. } => 
+            TaskRemindInfo {
+                title: title.into(),
+                info: info.into(),
+                user_id: *user_id,
+                priority: *priority,
+                challenge: *challenge,
+                deadline: None,
+                style: style.clone(),
+            },
+            Self::Deadline { user_id, title, info, priority, challenge, date, remind_at, style, .. } => 
             TaskRemindInfo {
                 title: title.into(),
                 info: info.into(),
                 user_id: *user_id,
+                priority: *priority,
+                challenge: *challenge,
+                deadline: Some(date.with_time(*remind_at).assume_utc()),
+                style: style.clone(),
             },
         }
     }
 
     pub fn on_days(&self) -> Option<&HashSet<Weekday>> {
     	match self {
-    		Self::Once { .. } => None,
+    		Self::Once { .. } | Self::Deadline { .. } | Self::Chained { .. } => None,
     		Self::Recurring { on_days, .. } => Some(on_days)
     	}
     }
@@ -175,21 +565,275 @@ impl Task {
 	/// UTC time
     pub fn datetime_utc(&self) -> Option<OffsetDateTime> {
     	match self {
-    		Self::Once { date, remind_at, .. } => {
+    		Self::Once { date, remind_at, .. }
+    		| Self::Deadline { date, remind_at, .. } => {
     			Some(date.with_time(*remind_at).assume_utc())
     		},
+    		Self::Chained { fire_at, .. } => *fire_at,
     		Self::Recurring { .. } => None
     	}
     }
 }
 
 pub struct TaskCreateInfo {
+    pub kind: TaskKind,
     pub title: String,
     pub info: String,
     pub remind_at: Time,
     pub date: Option<Date>,
     pub on_days: Option<HashSet<Weekday>>, 
     pub repeat_weekly: bool,
+    pub lead_times: Vec<Duration>,
+    pub tags: Vec<String>,
+    pub priority: Priority,
+    /// Has to be answered to stop the nag, if set
+    pub challenge: Option<ChallengeKind>,
+    /// Only used by deadlines
+    pub offsets: Vec<Duration>,
+    /// Only used by chained tasks, the task to wait for
+    pub after_task: Option<i64>,
+    /// Only used by chained tasks
+    pub delay: Duration,
+    /// Files from the message that created the task
+    pub attachments: Vec<Attachment>,
+    /// Parsed from the `key: value` lines of the info
+    pub style: EmbedStyle,
+}
+
+/// A file sent along with a task, re-attached whenever it fires.
+#[derive(Clone)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>
+}
+
+impl Attachment {
+    pub fn from_row_struct(row: AttachmentRow) -> Self {
+        Self {
+            filename: row.filename,
+            content_type: row.content_type,
+            data: row.data
+        }
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.content_type.as_deref().is_some_and(|t| t.starts_with("image/"))
+    }
+}
+
+/// A saved title/info/time combination to create tasks from.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub title: String,
+    pub info: String,
+    pub time_line: String
+}
+
+impl Template {
+    pub fn from_row_struct(row: TemplateRow) -> Self {
+        Self {
+            name: row.name,
+            title: row.title,
+            info: row.info,
+            time_line: row.time_line
+        }
+    }
+
+    /// The message that would create the task, optionally with a different time line.
+    pub fn to_text(&self, time_line: Option<&str>) -> String {
+        [self.title.as_str(), self.info.as_str(), time_line.unwrap_or(&self.time_line)]
+            .into_iter()
+            .filter(|l| !l.is_empty())
+            .join("\n")
+    }
+}
+
+/// Finds `#tags` in the text. Returned lowercase and without the `#`.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .filter_map(|word| word.strip_prefix('#'))
+        .map(|tag| {
+            tag.chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|tag| !tag.is_empty())
+        .unique()
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OccurrenceStatus {
+    Pending,
+    Done,
+    Missed,
+    Skipped
+}
+
+impl OccurrenceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Done => "done",
+            Self::Missed => "missed",
+            Self::Skipped => "skipped"
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(
+            match s {
+                "pending" => Self::Pending,
+                "done" => Self::Done,
+                "missed" => Self::Missed,
+                "skipped" => Self::Skipped,
+                _ => anyhow::bail!("Invalid occurrence status: {s}")
+            }
+        )
+    }
+}
+
+/// A single time a task was (or is about to be) reminded.
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    pub id: i64,
+    pub task_id: i64,
+    pub due_at: OffsetDateTime,
+    pub status: OccurrenceStatus,
+    /// 0 if the task info has no checklist
+    pub checklist_len: i32,
+    /// Still pending, but not nagged about before this
+    pub snoozed_until: Option<OffsetDateTime>,
+    /// Set while the user is being nagged about it
+    pub nag_started_at: Option<OffsetDateTime>
+}
+
+impl Occurrence {
+    pub fn from_row_struct(row: OccurrenceRow) -> Result<Self> {
+        Ok(
+            Self {
+                id: row.id,
+                task_id: row.task_id,
+                due_at: row.due_at,
+                status: OccurrenceStatus::parse(&row.status)?,
+                checklist_len: row.checklist_len,
+                snoozed_until: row.snoozed_until,
+                nag_started_at: row.nag_started_at
+            }
+        )
+    }
+}
+
+/// Whether the buddy agreed to be one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuddyStatus {
+    /// Waiting on the consent prompt
+    Asked,
+    Accepted,
+    Declined
+}
+
+impl BuddyStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Asked => "asked",
+            Self::Accepted => "accepted",
+            Self::Declined => "declined"
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(
+            match s {
+                "asked" => Self::Asked,
+                "accepted" => Self::Accepted,
+                "declined" => Self::Declined,
+                _ => anyhow::bail!("Invalid buddy status: {s}")
+            }
+        )
+    }
+}
+
+/// Someone who gets a dm when the user doesn't acknowledge a reminder in time.
+#[derive(Debug, Clone)]
+pub struct Buddy {
+    pub id: i64,
+    pub user_id: UserId,
+    pub buddy_id: UserId,
+    /// None for every task of the user without a buddy of its own
+    pub task_id: Option<i64>,
+    pub after: Duration,
+    pub status: BuddyStatus
+}
+
+impl Buddy {
+    pub fn from_row_struct(row: BuddyRow) -> Result<Self> {
+        Ok(
+            Self {
+                id: row.id,
+                user_id: UserId::new(row.user_id.parse::<u64>()?),
+                buddy_id: UserId::new(row.buddy_id.parse::<u64>()?),
+                task_id: row.task_id,
+                after: secs_to_duration(row.after_secs),
+                status: BuddyStatus::parse(&row.status)?
+            }
+        )
+    }
+
+    /// What the buddy looks after, for messages.
+    pub fn scope(&self) -> String {
+        match self.task_id {
+            Some(id) => format!("reminder `{id}`"),
+            None => "every reminder".into()
+        }
+    }
+}
+
+/// An occurrence the buddy should hear about if it's still pending at `tell_at`.
+#[derive(Debug, Clone)]
+pub struct BuddyCheck {
+    pub occurrence_id: i64,
+    pub user_id: UserId,
+    pub buddy_id: UserId,
+    pub title: String,
+    pub due_at: OffsetDateTime,
+    pub tell_at: OffsetDateTime
+}
+
+/// How hard the bot goes when a reminder fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    /// Just the embed, without a notification
+    Silent,
+    /// The embed and a single ping
+    Ping,
+    /// The embed, then pinging until acknowledged
+    #[default]
+    Nag
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Silent => "silent",
+            Self::Ping => "ping",
+            Self::Nag => "nag"
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(
+            match s {
+                "silent" => Self::Silent,
+                "ping" => Self::Ping,
+                "nag" => Self::Nag,
+                _ => anyhow::bail!("Invalid priority: {s}")
+            }
+        )
+    }
 }
 
 /// Contains all the necessary information for sending reminders.
@@ -197,5 +841,126 @@ pub struct TaskCreateInfo {
 pub struct TaskRemindInfo {
     pub title: String,
     pub info: String,
-    pub user_id: UserId
-}
\ No newline at end of file
+    pub user_id: UserId,
+    pub priority: Priority,
+    pub challenge: Option<ChallengeKind>,
+    pub deadline: Option<OffsetDateTime>,
+    pub style: EmbedStyle
+}
+/// Why a task stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveReason {
+    /// Ran out of occurrences and the last one was done
+    Completed,
+    /// Deleted by the user
+    Cancelled,
+    /// Ran out of occurrences without the last one being done
+    Expired
+}
+
+impl ArchiveReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Completed => "completed",
+            Self::Cancelled => "cancelled",
+            Self::Expired => "expired"
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(
+            match s {
+                "completed" => Self::Completed,
+                "cancelled" => Self::Cancelled,
+                "expired" => Self::Expired,
+                _ => anyhow::bail!("Invalid archive reason: {s}")
+            }
+        )
+    }
+}
+
+/// A task that doesn't run anymore but is kept for its history.
+pub struct ArchivedTask {
+    pub task: Task,
+    pub archived_at: OffsetDateTime,
+    pub reason: ArchiveReason
+}
+
+impl ArchivedTask {
+    pub fn from_row_struct(row: TaskRow) -> Result<Self> {
+        let archived_at = row.archived_at
+            .ok_or(anyhow::anyhow!("Task {} is not archived", row.id))?;
+        let reason = ArchiveReason::parse(row.archive_reason.as_deref().unwrap_or_default())?;
+        Ok(
+            Self {
+                task: Task::from_row_struct(row)?,
+                archived_at,
+                reason
+            }
+        )
+    }
+}
+
+/// Something a user did to their tasks that can be undone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Delete,
+    Edit,
+    Pause,
+    Resume
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Delete => "delete",
+            Self::Edit => "edit",
+            Self::Pause => "pause",
+            Self::Resume => "resume"
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(
+            match s {
+                "create" => Self::Create,
+                "delete" => Self::Delete,
+                "edit" => Self::Edit,
+                "pause" => Self::Pause,
+                "resume" => Self::Resume,
+                _ => anyhow::bail!("Invalid action: {s}")
+            }
+        )
+    }
+}
+
+/// An action that was just undone.
+/// `tasks` are the removed tasks for a create, the restored ones otherwise.
+pub struct Undone {
+    pub action: Action,
+    pub tasks: Vec<Task>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_durations_as_seconds() {
+        let durations = vec![Duration::days(7), Duration::minutes(90)];
+        let secs = durations_to_secs(&durations).unwrap();
+        assert_eq!(secs, vec![604800, 5400]);
+        assert_eq!(secs_to_durations(&secs), durations);
+    }
+
+    #[test]
+    fn turns_away_durations_that_dont_fit() {
+        assert!(duration_to_secs(Duration::seconds(i32::MAX as i64)).is_ok());
+        assert!(duration_to_secs(Duration::seconds(i32::MAX as i64 + 1)).is_err());
+        assert!(durations_to_secs(&[Duration::hours(1), Duration::days(99999999999999)]).is_err());
+        assert!(duration_to_millis(Duration::days(24)).is_ok());
+        assert!(duration_to_millis(Duration::days(25)).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use serenity::all::{ChannelId, CreateMessage, EditMessage, Http, HttpError, Message, MessageId, StatusCode};
+use tokio::{sync::{mpsc::{self, error::TrySendError}, oneshot, watch}, time::{self, Instant}};
+
+/// Discord allows 50 requests a second per bot, the rest is left for command replies and buttons.
+const SENDS_PER_SECOND: u32 = 25;
+/// Nag pings past this many waiting are dropped, there's another one coming anyway.
+const NAG_QUEUE_LEN: usize = 100;
+/// How long every delivery waits after a 429 that didn't say how long to wait.
+const RATE_LIMIT_PAUSE: Duration = Duration::from_secs(5);
+const MAX_ATTEMPTS: u32 = 4;
+/// Doubled after every failed attempt.
+const RETRY_BASE: Duration = Duration::from_secs(2);
+
+/// Which queue a delivery waits in, first deliveries always go out before nags.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Lane {
+    /// Reminders, heads-ups and anything else the user only gets once
+    First,
+    /// Repeat pings, these aren't retried since the next one is on its way
+    Nag,
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Send(ChannelId, CreateMessage),
+    Edit(ChannelId, MessageId, EditMessage),
+    Delete(ChannelId, MessageId),
+}
+
+struct Delivery {
+    op: Op,
+    lane: Lane,
+    attempt: u32,
+    done: oneshot::Sender<Result<Option<Message>>>,
+    _in_flight: InFlight,
+}
+
+/// Counts a delivery as in flight until it's sent or given up on, however it ends.
+struct InFlight(Arc<watch::Sender<usize>>);
+
+impl InFlight {
+    fn new(count: &Arc<watch::Sender<usize>>) -> Self {
+        count.send_modify(|n| *n += 1);
+        InFlight(count.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.send_modify(|n| *n -= 1);
+    }
+}
+
+/// Every message the bot sends on its own goes through here, so that
+/// all users share one send budget and a failed send never takes a job down with it.
+/// Replies to commands and buttons go straight to Discord.
+#[derive(Clone)]
+pub struct Outbox {
+    first: mpsc::UnboundedSender<Delivery>,
+    nags: mpsc::Sender<Delivery>,
+    in_flight: Arc<watch::Sender<usize>>,
+    /// Nothing goes out before this
+    paused_until: Arc<watch::Sender<Instant>>,
+}
+
+/// Sends what's queued in the outbox, started once the bot is connected.
+pub struct OutboxWorker {
+    first: mpsc::UnboundedReceiver<Delivery>,
+    nags: mpsc::Receiver<Delivery>,
+    /// Puts failed deliveries back in line
+    retry: mpsc::UnboundedSender<Delivery>,
+    paused_until: Arc<watch::Sender<Instant>>,
+}
+
+impl Outbox {
+    pub fn new() -> (Self, OutboxWorker) {
+        let (first, first_rx) = mpsc::unbounded_channel();
+        let (nags, nags_rx) = mpsc::channel(NAG_QUEUE_LEN);
+        let (paused_until, _) = watch::channel(Instant::now());
+        let paused_until = Arc::new(paused_until);
+        let worker = OutboxWorker {
+            first: first_rx,
+            nags: nags_rx,
+            retry: first.clone(),
+            paused_until: paused_until.clone()
+        };
+        let (in_flight, _) = watch::channel(0);
+        (Outbox { first, nags, in_flight: Arc::new(in_flight), paused_until }, worker)
+    }
+
+    /// Holds back every delivery for `wait`, for when Discord says how long we're rate limited.
+    pub fn pause(&self, wait: Duration) {
+        pause(&self.paused_until, wait);
+    }
+
+    pub async fn send(&self, channel: ChannelId, message: CreateMessage, lane: Lane) -> Result<Message> {
+        self.submit(Op::Send(channel, message), lane)
+            .await?
+            .ok_or(anyhow!("Discord didn't return the sent message"))
+    }
+
+    pub async fn edit(&self, channel: ChannelId, message: MessageId, edit: EditMessage, lane: Lane) -> Result<Message> {
+        self.submit(Op::Edit(channel, message, edit), lane)
+            .await?
+            .ok_or(anyhow!("Discord didn't return the edited message"))
+    }
+
+    pub async fn delete(&self, channel: ChannelId, message: MessageId, lane: Lane) -> Result<()> {
+        self.submit(Op::Delete(channel, message), lane).await?;
+        Ok(())
+    }
+
+    /// Waits for everything queued so far to be sent or given up on, for at most `timeout`.
+    /// Returns false if some were still left.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let mut in_flight = self.in_flight.subscribe();
+        let drained = time::timeout(timeout, in_flight.wait_for(|n| *n == 0)).await;
+        drained.is_ok()
+    }
+
+    /// Waits until the delivery went out or failed for good.
+    async fn submit(&self, op: Op, lane: Lane) -> Result<Option<Message>> {
+        let (done, result) = oneshot::channel();
+        let delivery = Delivery { op, lane, attempt: 1, done, _in_flight: InFlight::new(&self.in_flight) };
+        match lane {
+            Lane::First => self.first.send(delivery)
+                .map_err(|_| anyhow!("The outbox is closed"))?,
+            Lane::Nag => self.nags.try_send(delivery)
+                .map_err(|e| match e {
+                    TrySendError::Full(_) => anyhow!("Too many nags waiting to be sent, dropped this one"),
+                    TrySendError::Closed(_) => anyhow!("The outbox is closed"),
+                })?,
+        }
+        result.await
+            .map_err(|_| anyhow!("The outbox dropped the delivery"))?
+    }
+}
+
+impl OutboxWorker {
+    pub async fn run(mut self, http: Arc<Http>) {
+        let gap = Duration::from_secs(1) / SENDS_PER_SECOND;
+        let mut next_slot = Instant::now();
+        loop {
+            // wait for a free slot before picking, so a reminder queued meanwhile still goes first
+            time::sleep_until(next_slot).await;
+            wait_out_pause(&self.paused_until).await;
+
+            let delivery = tokio::select! {
+                biased;
+                Some(delivery) = self.first.recv() => delivery,
+                Some(delivery) = self.nags.recv() => delivery,
+                else => return,
+            };
+            // a rate limit may have come up while the queue was empty
+            wait_out_pause(&self.paused_until).await;
+            next_slot = Instant::now() + gap;
+
+            // on its own task, serenity waits out per-channel limits and one slow DM shouldn't hold up the rest
+            let (http, paused_until, retry) = (http.clone(), self.paused_until.clone(), self.retry.clone());
+            tokio::spawn(async move {
+                perform(http, delivery, &paused_until, retry).await;
+            });
+        }
+    }
+}
+
+/// Sleeps until the pause is over, checking again after waking since it may have been pushed back.
+async fn wait_out_pause(paused_until: &watch::Sender<Instant>) {
+    loop {
+        let until = *paused_until.borrow();
+        if until <= Instant::now() {
+            return;
+        }
+        time::sleep_until(until).await;
+    }
+}
+
+/// Never shortens a pause that's already longer.
+fn pause(paused_until: &watch::Sender<Instant>, wait: Duration) {
+    let until = Instant::now() + wait;
+    paused_until.send_modify(|p| *p = (*p).max(until));
+}
+
+async fn perform(
+    http: Arc<Http>,
+    delivery: Delivery,
+    paused_until: &watch::Sender<Instant>,
+    retry: mpsc::UnboundedSender<Delivery>
+) {
+    let Delivery { op, lane, attempt, done, _in_flight } = delivery;
+    let res = match &op {
+        Op::Send(channel, message) => channel.send_message(&http, message.clone())
+            .await
+            .map(Some),
+        Op::Edit(channel, id, edit) => channel.edit_message(&http, *id, edit.clone())
+            .await
+            .map(Some),
+        Op::Delete(channel, id) => channel.delete_message(&http, *id)
+            .await
+            .map(|_| None),
+    };
+
+    let e = match res {
+        Ok(message) => {
+            let _ = done.send(Ok(message));
+            return;
+        }
+        Err(e) => e
+    };
+
+    let status = match &e {
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(res)) => Some(res.status_code),
+        _ => None
+    };
+    // serenity retries 429s itself and reports how long to wait, see `DZBot::ratelimit`,
+    // so one only ends up here without a retry-after
+    if status == Some(StatusCode::TOO_MANY_REQUESTS) {
+        eprintln!("Rate limited by Discord, pausing deliveries for {}s", RATE_LIMIT_PAUSE.as_secs());
+        pause(paused_until, RATE_LIMIT_PAUSE);
+    }
+
+    // client errors like a closed DM won't go away by trying again
+    let transient = match status {
+        Some(status) => status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+        None => matches!(e, serenity::Error::Http(HttpError::Request(_))),
+    };
+    if transient && lane == Lane::First && attempt < MAX_ATTEMPTS {
+        let wait = RETRY_BASE * 2u32.pow(attempt - 1);
+        eprintln!("Delivery failed (attempt {attempt}/{MAX_ATTEMPTS}), retrying in {}s: {e}", wait.as_secs());
+        tokio::spawn(async move {
+            time::sleep(wait).await;
+            // the outbox is gone when this fails, dropping `done` tells the sender
+            let _ = retry.send(Delivery { op, lane, attempt: attempt + 1, done, _in_flight });
+        });
+    } else {
+        eprintln!("Delivery failed after {attempt} attempt(s), giving up: {e}");
+        let _ = done.send(Err(e.into()));
+    }
+}
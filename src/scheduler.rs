@@ -2,7 +2,7 @@ use serenity::all::Http;
 use std::{sync::Arc, time::Duration as StdDuration};
 use anyhow::Result;
 
-use crate::{bot::DzContext, jobs::{EmbedReminderJob, SpamPingJob, SpamPingSignal}, model::{Task, TaskRemindInfo}};
+use crate::{bot::DzContext, jobs::EmbedReminderJob, model::{Task, TaskRemindInfo}};
 
 pub struct TaskScheduler {
     ctx: DzContext
@@ -13,8 +13,7 @@ impl TaskScheduler {
         TaskScheduler { ctx }
     }
 
-    // Returns a channel.
-    // Upon sending any value to this channel, if active, the spam routine will stop.
+    // Starts the reminder job of the task, replacing any old one.
     pub async fn add_task(&self, http: Arc<Http>, task: &Task) -> Result<()> {
         
         // let days_str = task.on_days.clone().into_iter().map(|d| i32::from(d).to_string())
@@ -26,25 +25,10 @@ impl TaskScheduler {
         // let (to_ctl, from_task) = watch::channel(false);
 
         println!("Adding task: {task:?}");
-        let (task_id, uid) = match task {
-            Task::Once { id, user_id, .. } | Task::Recurring { id, user_id, .. } => (id, user_id),
-        };
-
         let mut ctx = self.ctx.write().await;
-
-        // insert a spammer controller if there isnt one
-        ctx.spammer_ctl.entry(*uid)
-            .or_insert_with(|| {
-                SpamPingJob::new(
-                    self.ctx.clone(), 
-                    http.clone(),
-                    *uid
-                )
-            });
-
         ctx.reminders_ctl
             .insert(
-                *task_id, 
+                task.id(), 
                 EmbedReminderJob::new(self.ctx.clone(), http, task)
             );
         
@@ -0,0 +1,137 @@
+use time::{Duration, OffsetDateTime};
+
+use crate::model::{Occurrence, OccurrenceStatus};
+
+/// Habit statistics computed from the occurrence history of a recurring task.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HabitStats {
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// (done, total) over the last 7 days
+    pub week: (u32, u32),
+    /// (done, total) over the last 30 days
+    pub month: (u32, u32),
+}
+
+impl HabitStats {
+    /// `occurrences` must be sorted oldest first.
+    /// Pending occurrences haven't been decided yet and skipped ones were never meant to happen,
+    /// so both are ignored.
+    pub fn compute(occurrences: &[Occurrence], now: OffsetDateTime) -> Self {
+        let mut stats = Self::default();
+        let week_start = now - Duration::days(7);
+        let month_start = now - Duration::days(30);
+
+        for occ in occurrences {
+            let done = match occ.status {
+                OccurrenceStatus::Pending | OccurrenceStatus::Skipped => continue,
+                OccurrenceStatus::Done => true,
+                OccurrenceStatus::Missed => false,
+            };
+
+            if done {
+                stats.current_streak += 1;
+                stats.longest_streak = stats.longest_streak.max(stats.current_streak);
+            } else {
+                stats.current_streak = 0;
+            }
+
+            if occ.due_at >= week_start {
+                stats.week.1 += 1;
+                stats.week.0 += done as u32;
+            }
+            if occ.due_at >= month_start {
+                stats.month.1 += 1;
+                stats.month.0 += done as u32;
+            }
+        }
+
+        stats
+    }
+
+    pub fn footer(&self) -> String {
+        format!(
+            "streak: {} (best {}) • week: {} • month: {}",
+            self.current_streak,
+            self.longest_streak,
+            rate_str(self.week),
+            rate_str(self.month),
+        )
+    }
+}
+
+fn rate_str((done, total): (u32, u32)) -> String {
+    if total == 0 {
+        return "-".into();
+    }
+    format!("{done}/{total} ({}%)", done * 100 / total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn occurrence(due_at: OffsetDateTime, status: OccurrenceStatus) -> Occurrence {
+        Occurrence {
+            id: 1,
+            task_id: 1,
+            due_at,
+            status,
+            checklist_len: 0,
+            snoozed_until: None,
+            nag_started_at: None
+        }
+    }
+
+    /// One a day, the last one yesterday.
+    fn daily(now: OffsetDateTime, statuses: &[OccurrenceStatus]) -> Vec<Occurrence> {
+        statuses.iter()
+            .enumerate()
+            .map(|(i, s)| occurrence(now - Duration::days((statuses.len() - i) as i64), *s))
+            .collect()
+    }
+
+    use OccurrenceStatus::{Done, Missed, Pending, Skipped};
+
+    #[test]
+    fn a_miss_resets_the_streak_but_not_the_best() {
+        let now = OffsetDateTime::now_utc();
+        let stats = HabitStats::compute(&daily(now, &[Done, Done, Done, Missed, Done]), now);
+        assert_eq!(stats.current_streak, 1);
+        assert_eq!(stats.longest_streak, 3);
+
+        let stats = HabitStats::compute(&daily(now, &[Done, Missed]), now);
+        assert_eq!(stats.current_streak, 0);
+        assert_eq!(stats.longest_streak, 1);
+    }
+
+    #[test]
+    fn skipped_and_pending_dont_count() {
+        let now = OffsetDateTime::now_utc();
+        let stats = HabitStats::compute(&daily(now, &[Done, Skipped, Done, Pending]), now);
+        assert_eq!(stats.current_streak, 2);
+        assert_eq!(stats.week, (2, 2));
+        assert_eq!(stats.month, (2, 2));
+    }
+
+    #[test]
+    fn rates_only_look_at_their_window() {
+        let now = OffsetDateTime::now_utc();
+        let occurrences = vec![
+            occurrence(now - Duration::days(40), Done),
+            occurrence(now - Duration::days(20), Missed),
+            occurrence(now - Duration::days(10), Done),
+            occurrence(now - Duration::days(3), Done),
+        ];
+        let stats = HabitStats::compute(&occurrences, now);
+        assert_eq!(stats.week, (1, 1));
+        assert_eq!(stats.month, (2, 3));
+    }
+
+    #[test]
+    fn nothing_to_go_by_yet() {
+        let stats = HabitStats::compute(&[], OffsetDateTime::now_utc());
+        assert_eq!(stats.current_streak, 0);
+        assert_eq!(stats.footer(), "streak: 0 (best 0) • week: - • month: -");
+    }
+}
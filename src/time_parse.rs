@@ -1,7 +1,8 @@
 use std::{collections::HashSet, u8};
 use chrono::{Local, NaiveTime, Timelike};
 use itertools::Itertools;
-use time::{macros::format_description, Date, Month, OffsetDateTime, Time, UtcOffset, Weekday};
+use crate::{challenge::ChallengeKind, model::Priority};
+use time::{macros::format_description, Date, Duration, Month, OffsetDateTime, Time, UtcOffset, Weekday};
 
 fn parse_dayofweek(c: char) -> Option<Weekday> {
     match c {
@@ -69,11 +70,10 @@ fn parse_remind_at(token: &String) -> Result<(Time, DayShift), String> {
         format_description!("[hour repr:12 padding:none][period]"),
         format_description!("[hour padding:none]:[minute]"),
     ];
+    // quietly, any line of a bulk message gets tried as a time line
     for parser in parsers {
         match Time::parse(&upper, parser) {
-            Err(e) => {
-                eprintln!("trying to parse again due to err {e}");
-            },
+            Err(_) => {},
             Ok(t) => {
                 let offset_sec = Local::now()
                     .offset()
@@ -110,53 +110,228 @@ fn parse_date(token: &str, time: Time, day_shift: &DayShift) -> Option<Date> {
 
     let now = OffsetDateTime::now_utc();
     let curr_year = now.year();
-    Date::from_calendar_date(curr_year, Month::try_from(month).unwrap(), day)
-    .map(|d| {
-        // account for day going over bounds when converting from local to UTC.
-        let date = match day_shift {
-            DayShift::Forward => d.next_day().expect("the end of time"),
-            DayShift::Backward => d.previous_day().expect("the end of time"),
-            DayShift::None => d,
-        };
-        let date_time = date.with_time(time).assume_utc();
-        if date_time <= now {
-            // if the date is before rn, construct with next year
-            date.replace_year(curr_year + 1).unwrap()
-        } else {
-            date
-        }
-    }).ok()
+    let d = Date::from_calendar_date(curr_year, Month::try_from(month).ok()?, day).ok()?;
+    // account for day going over bounds when converting from local to UTC.
+    let date = match day_shift {
+        DayShift::Forward => d.next_day().expect("the end of time"),
+        DayShift::Backward => d.previous_day().expect("the end of time"),
+        DayShift::None => d,
+    };
+    let date_time = date.with_time(time).assume_utc();
+    if date_time <= now {
+        // if the date is before rn, construct with next year, which may not have a 2/29
+        date.replace_year(curr_year + 1).ok()
+    } else {
+        Some(date)
+    }
 }
 
 fn parse_repeat_weekly(token: &str) -> bool {
     token.to_lowercase().contains("rep")
 }
 
+/// Parses durations like `15m`, `1h`, `2d` or `1h30m`.
+pub fn parse_duration(token: &str) -> Option<Duration> {
+    let mut total = Duration::ZERO;
+    let mut num = String::new();
+    for c in token.trim().to_lowercase().chars() {
+        if c.is_ascii_digit() {
+            num.push(c);
+            continue;
+        }
+        let n: i64 = num.parse().ok()?;
+        num.clear();
+        let unit_secs = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None
+        };
+        // None rather than a panic on absurd numbers
+        total = total.checked_add(Duration::seconds(n.checked_mul(unit_secs)?))?;
+    }
+    // trailing number without a unit
+    if !num.is_empty() || total.is_zero() {
+        return None;
+    }
+    Some(total)
+}
+
+/// Formats a duration the way [`parse_duration`] would accept it.
+pub fn format_duration(dur: Duration) -> String {
+    let mut secs = dur.whole_seconds();
+    let mut out = String::new();
+    for (unit, len) in [('d', 86400), ('h', 3600), ('m', 60), ('s', 1)] {
+        if secs >= len {
+            out.push_str(&format!("{}{unit}", secs / len));
+            secs %= len;
+        }
+    }
+    if out.is_empty() {
+        out.push_str("0s");
+    }
+    out
+}
+
+/// Parses the comma separated durations following the `pre` keyword.
+fn parse_lead_times(tokens: &[String]) -> Result<Vec<Duration>, String> {
+    let Some(pos) = tokens.iter().position(|t| t.eq_ignore_ascii_case("pre")) else {
+        return Ok(vec![]);
+    };
+    let list = tokens.get(pos + 1).ok_or(String::from("pre needs lead times, like `pre 15m,1h`"))?;
+    parse_duration_list(list)
+}
+
+/// Parses comma separated durations, like `7d,1d,3h`.
+pub fn parse_duration_list(list: &str) -> Result<Vec<Duration>, String> {
+    list.split(',')
+        .map(|t| parse_duration(t).ok_or(format!("could not parse duration `{}`", t.trim())))
+        .collect()
+}
+
+/// The last of `silent`, `ping` or `nag`, defaulting to nag.
+fn parse_priority(tokens: &[String]) -> Priority {
+    tokens.iter()
+        .rev()
+        .find_map(|t| Priority::parse(&t.to_lowercase()).ok())
+        .unwrap_or_default()
+}
+
+/// The last of `math` or `code`, if any.
+fn parse_challenge(tokens: &[String]) -> Option<ChallengeKind> {
+    tokens.iter()
+        .rev()
+        .find_map(|t| ChallengeKind::parse(&t.to_lowercase()).ok())
+}
+
 pub struct TaskTimeInfo {
     pub remind_time: Time,
     pub days_of_week: Option<HashSet<Weekday>>,
     pub repeat_weekly: bool,
-    pub date: Option<Date>
+    pub date: Option<Date>,
+    pub lead_times: Vec<Duration>,
+    pub priority: Priority,
+    pub challenge: Option<ChallengeKind>,
+    pub deadline: bool
 }
 
 impl TaskTimeInfo {
     pub fn parse(str: &str) -> Result<Self, String> {
-        let tokens: Vec<String> = str.split(' ').map(String::from).collect();
+        let mut tokens: Vec<String> = str.split(' ').map(String::from).collect();
+
+        // `deadline 11/30 5pm`, the time and date can be either way around
+        let deadline = tokens[0].eq_ignore_ascii_case("deadline");
+        if deadline {
+            tokens.remove(0);
+            if tokens.len() >= 2 && parse_remind_at(&tokens[0]).is_err() {
+                tokens.swap(0, 1);
+            }
+        }
 
         if tokens.len() < 2 {
             return Err("are you stupid you must be stupid".into());
         }
 
         let (remind_time, day_shift) = parse_remind_at(&tokens[0])?;
+        let date = parse_date(&tokens[1], remind_time, &day_shift);
+
+        if deadline && date.is_none() {
+            return Err("deadlines need a date, like `deadline 11/30 5pm`".into());
+        }
 
         Ok(
             Self {
                 // parse same token for date and days of week.
-                days_of_week: parse_on_days(&tokens[1],&day_shift), 
-                date: parse_date(&tokens[1], remind_time, &day_shift),
+                days_of_week: (!deadline).then(|| parse_on_days(&tokens[1],&day_shift)).flatten(), 
+                date,
                 repeat_weekly: parse_repeat_weekly(&tokens.get(2).unwrap_or(&String::new())),
+                lead_times: parse_lead_times(&tokens[2..])?,
+                priority: parse_priority(&tokens[2..]),
+                challenge: parse_challenge(&tokens[2..]),
+                deadline,
                 remind_time 
             }
         )
     }
-}
\ No newline at end of file
+}
+
+/// The last line of a chained task, like `after 12 45m`.
+pub struct ChainTimeInfo {
+    /// Id of the task it waits for
+    pub after: i64,
+    pub delay: Duration,
+    pub lead_times: Vec<Duration>,
+    pub priority: Priority,
+    pub challenge: Option<ChallengeKind>
+}
+
+impl ChainTimeInfo {
+    pub fn parse(str: &str) -> Result<Self, String> {
+        let tokens: Vec<String> = str.split_whitespace().map(String::from).collect();
+        if !tokens.first().is_some_and(|t| t.eq_ignore_ascii_case("after")) {
+            return Err("chained reminders start with `after`".into());
+        }
+        if tokens.len() < 3 {
+            return Err("chained reminders look like `after 12 45m`, the id of the task then the delay".into());
+        }
+
+        Ok(
+            Self {
+                after: tokens[1].parse()
+                    .map_err(|_| format!("`{}` is not a task id", tokens[1]))?,
+                delay: parse_duration(&tokens[2])
+                    .ok_or(format!("could not parse duration `{}`", tokens[2]))?,
+                lead_times: parse_lead_times(&tokens[3..])?,
+                priority: parse_priority(&tokens[3..]),
+                challenge: parse_challenge(&tokens[3..])
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_combined_units() {
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration(" 2W "), Some(Duration::weeks(2)));
+        assert_eq!(parse_duration("1d1s"), Some(Duration::seconds(86401)));
+    }
+
+    #[test]
+    fn rejects_missing_or_unknown_units() {
+        assert_eq!(parse_duration("15"), None);
+        assert_eq!(parse_duration("1h5"), None);
+        assert_eq!(parse_duration("3y"), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("0m"), None);
+    }
+
+    #[test]
+    fn rejects_overflow_instead_of_panicking() {
+        assert_eq!(parse_duration("99999999999999999d"), None);
+        assert_eq!(parse_duration("9223372036854775807w"), None);
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+    }
+
+    #[test]
+    fn rejects_impossible_dates() {
+        let noon = Time::from_hms(12, 0, 0).unwrap();
+        assert_eq!(parse_date("13/40", noon, &DayShift::None), None);
+        assert_eq!(parse_date("0/1", noon, &DayShift::None), None);
+        assert_eq!(parse_date("2/30", noon, &DayShift::None), None);
+        assert!(parse_date("12/25", noon, &DayShift::None).is_some());
+        assert!(TaskTimeInfo::parse("deadline 13/40 9am").is_err());
+    }
+
+    #[test]
+    fn formats_what_it_parses() {
+        for text in ["45s", "1h30m", "2d3h", "1d1s"] {
+            assert_eq!(format_duration(parse_duration(text).unwrap()), text);
+        }
+    }
+}